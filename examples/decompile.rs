@@ -0,0 +1,12 @@
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+
+    let world_file = args.next().unwrap_or_else(|| String::from("output/world.bin"));
+    let output_dir = args.next().unwrap_or_else(|| String::from("output/decompiled"));
+
+    if let Err(err) = world_builder::decompile::decompile_file(&world_file, &output_dir) {
+        eprintln!("Could not decompile world file at {} with error {}", world_file, err);
+    }
+
+}
@@ -0,0 +1,18 @@
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+
+    let (old, new) = match (args.next(), args.next()) {
+        (Some(old), Some(new)) => (old, new),
+        _ => {
+            eprintln!("Usage: diff <old world file> <new world file>");
+            return;
+        }
+    };
+
+    match world_builder::diff::diff_files(&old, &new) {
+        Ok(diff) => print!("{}", diff),
+        Err(err) => eprintln!("Could not compare world files with error {}", err),
+    }
+
+}
@@ -0,0 +1,23 @@
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+
+    let world_file = args.next().unwrap_or_else(|| String::from("output/world.bin"));
+    let species: u16 = args.next().map(|species| species.parse().expect("Could not parse number of species")).unwrap_or(386);
+
+    let (_, world) = match world_builder::output::read_world_file(&world_file) {
+        Ok(world) => world,
+        Err(err) => {
+            eprintln!("Could not read world file at {} with error {}", world_file, err);
+            return;
+        }
+    };
+
+    let report = world_builder::report::encounters::encounter_report(&world, 1..=species);
+
+    std::fs::write("output/encounters.csv", report.to_csv()).unwrap_or_else(|err| panic!("Could not write encounter report with error {}", err));
+    std::fs::write("output/encounters.md", report.to_markdown()).unwrap_or_else(|err| panic!("Could not write encounter report with error {}", err));
+
+    println!("Wrote encounter report with {} rows, {} species appear nowhere", report.rows.len(), report.missing.len());
+
+}
@@ -0,0 +1,31 @@
+use world_builder::import::decomp::{DecompImport, import_decomp};
+
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+
+    let project = args.next().unwrap_or_else(|| String::from("pokefirered"));
+    let output_dir = args.next().unwrap_or_else(|| String::from("imported"));
+    let tilesets = args.next().unwrap_or_else(|| String::from("tilesets.toml"));
+    let npc_types = args.next().unwrap_or_else(|| String::from("npc_types.toml"));
+
+    // pairs of tileset labels and palette ids, such as gTileset_General = 0
+    let tileset_palettes = toml::from_str(
+        &std::fs::read_to_string(&tilesets).unwrap_or_else(|err| panic!("Could not read tileset palettes at {} with error {}", tilesets, err))
+    ).unwrap_or_else(|err| panic!("Could not parse tileset palettes at {} with error {}", tilesets, err));
+
+    // pairs of object event graphics and NPC types, such as OBJ_EVENT_GFX_BOY_1 = "boy"
+    let npc_types = match std::fs::read_to_string(&npc_types) {
+        Ok(content) => toml::from_str(&content).unwrap_or_else(|err| panic!("Could not parse NPC types at {} with error {}", npc_types, err)),
+        Err(err) => {
+            eprintln!("Could not read NPC types at {} with error {}, so no NPCs will be imported", npc_types, err);
+            Default::default()
+        }
+    };
+
+    import_decomp(project, output_dir, &DecompImport {
+        tileset_palettes,
+        npc_types,
+    });
+
+}
@@ -0,0 +1,10 @@
+fn main() {
+
+    let path = std::env::args().nth(1).unwrap_or_else(|| String::from("output/world.bin"));
+
+    match world_builder::inspect::inspect(&path) {
+        Ok(summary) => print!("{}", summary),
+        Err(err) => eprintln!("Could not inspect world file at {} with error {}", path, err),
+    }
+
+}
@@ -0,0 +1,24 @@
+fn main() {
+
+    let mut args = std::env::args().skip(1);
+
+    let world_file = args.next().unwrap_or_else(|| String::from("output/world.bin"));
+    let music_table = args.next().unwrap_or_else(|| String::from("music.toml"));
+
+    let (_, world) = match world_builder::output::read_world_file(&world_file) {
+        Ok(world) => world,
+        Err(err) => {
+            eprintln!("Could not read world file at {} with error {}", world_file, err);
+            return;
+        }
+    };
+
+    let music = world_builder::music::MusicTable::load(&music_table);
+
+    let report = world_builder::report::music::music_report(&world, &music);
+
+    std::fs::write("output/music.md", report.to_markdown()).unwrap_or_else(|err| panic!("Could not write music report with error {}", err));
+
+    println!("Wrote music report for {} tracks, {} are not played by any map", report.tracks.len(), report.tracks.iter().filter(|track| track.maps.is_empty()).count());
+
+}
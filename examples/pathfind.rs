@@ -4,7 +4,8 @@ use firecore_util::Coordinate;
 use firecore_util::Direction;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let world: firecore_world_lib::serialized::SerializedWorld = postcard::from_bytes(&std::fs::read("output/world.bin")?)?;
+    let (_, compiled) = world_builder::output::read_world_file("output/world.bin")?;
+    let world = compiled.world;
 
     let set = "pallet_houses".parse().unwrap();
     let map = "oak_lab".parse().unwrap();
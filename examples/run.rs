@@ -1,5 +1,4 @@
 use std::time::Instant;
-use firecore_world_lib::serialized::SerializedWorld;
 
 static OUTPUT: &str = "output/world.bin";
 
@@ -11,11 +10,10 @@ fn main() {
 
     match std::fs::read(OUTPUT) {
         Ok(bytes) => {
-            let result: Result<SerializedWorld, postcard::Error> = postcard::from_bytes(&bytes);
-            match result {
-                Ok(world) => {
-                    println!("Successfully decoded serialized world!");
-                    for palette in &world.palettes {
+            match world_builder::output::read_world(&bytes) {
+                Ok((header, world)) => {
+                    println!("Successfully decoded serialized world! (builder {}, world lib {})", header.builder_version, header.world_lib_version);
+                    for palette in &world.world.palettes {
                         if palette.id == 0 {
                             match std::fs::read("world/textures/Palette0B.png") {
                                 Ok(bytes) => {
@@ -0,0 +1,292 @@
+//! World data compiled by this builder that `SerializedWorld` has no place for.
+//! It is written next to the serialized world in the world file payload.
+
+use serde::{Serialize, Deserialize};
+use ahash::AHashMap as HashMap;
+
+use firecore_world_lib::TileId;
+use firecore_world_lib::map::{
+    MapIdentifier,
+    wild::{WildEntry, table::WildPokemonTable},
+};
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct WorldExtensions {
+
+    pub chunks: HashMap<MapIdentifier, MapExtensions>,
+    pub map_sets: HashMap<MapIdentifier, HashMap<MapIdentifier, MapExtensions>>,
+    /// Top layer image of each palette that has one, by palette id. It is drawn above characters.
+    pub top_palettes: HashMap<u8, Vec<u8>>,
+    /// Animated tiles, sorted by tile id.
+    pub animations: Vec<TileAnimation>,
+    /// Behavior of each tile that has one, by global tile id.
+    pub tile_behaviors: HashMap<TileId, TileBehavior>,
+
+}
+
+/// Frames a renderer cycles through in place of a tile.
+#[derive(Serialize, Deserialize)]
+pub struct TileAnimation {
+
+    /// Global tile id
+    pub tile: TileId,
+    /// PNG strip of 16x16 frames laid out left to right
+    pub frames: Vec<u8>,
+    /// Seconds each frame is shown for
+    pub duration: f32,
+
+}
+
+#[derive(Default, Serialize, Deserialize)]
+pub struct MapExtensions {
+
+    /// Encounter tables besides the grass table, which stays in `WorldMap::wild`.
+    pub encounters: HashMap<EncounterKind, WildEntry>,
+    /// Tables that replace the base table of their kind during a time period and/or season.
+    /// They trigger on the same tiles as the base table.
+    pub variants: Vec<EncounterVariant>,
+    pub header: MapHeader,
+
+}
+
+/// Fields of the gba map header besides the size, palettes, music and border.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MapHeader {
+
+    pub weather: Weather,
+    pub map_type: MapType,
+    /// Whether the map's name pops up when entering it
+    pub show_name: bool,
+    /// Background battles on the map take place in
+    pub battle_scene: u8,
+
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weather {
+    #[default]
+    None,
+    SunnyClouds,
+    Sunny,
+    Rain,
+    Snow,
+    Thunderstorm,
+    FogHorizontal,
+    VolcanicAsh,
+    Sandstorm,
+    FogDiagonal,
+    Underwater,
+    Shade,
+    Drought,
+    Downpour,
+    UnderwaterBubbles,
+    /// A weather id this builder does not know the name of
+    Other(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MapType {
+    #[default]
+    None,
+    Town,
+    City,
+    Route,
+    Underground,
+    Underwater,
+    OceanRoute,
+    Indoor,
+    SecretBase,
+    /// A map type id this builder does not know the name of
+    Other(u8),
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct EncounterVariant {
+
+    pub kind: EncounterKind,
+    pub time: Option<TimePeriod>,
+    pub season: Option<Season>,
+    pub table: WildPokemonTable,
+
+}
+
+/// What happens when the player walks onto (or faces) a tile.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TileBehavior {
+    TallGrass,
+    Water,
+    Waterfall,
+    LedgeUp,
+    LedgeDown,
+    LedgeLeft,
+    LedgeRight,
+    Ice,
+    Door,
+    Counter,
+    Sand,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimePeriod {
+    Morning,
+    Day,
+    Night,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+/// Ways a wild encounter can be triggered. Each has its own table under a map's `wild` directory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EncounterKind {
+    Grass,
+    Surf,
+    OldRod,
+    GoodRod,
+    SuperRod,
+    RockSmash,
+    Cave,
+}
+
+impl EncounterKind {
+
+    pub const ALL: [EncounterKind; 7] = [
+        EncounterKind::Grass,
+        EncounterKind::Surf,
+        EncounterKind::OldRod,
+        EncounterKind::GoodRod,
+        EncounterKind::SuperRod,
+        EncounterKind::RockSmash,
+        EncounterKind::Cave,
+    ];
+
+    /// Name of the table file under a map's `wild` directory, without extension.
+    pub fn name(&self) -> &'static str {
+        match self {
+            EncounterKind::Grass => "grass",
+            EncounterKind::Surf => "surf",
+            EncounterKind::OldRod => "old_rod",
+            EncounterKind::GoodRod => "good_rod",
+            EncounterKind::SuperRod => "super_rod",
+            EncounterKind::RockSmash => "rock_smash",
+            EncounterKind::Cave => "cave",
+        }
+    }
+
+}
+
+impl TimePeriod {
+
+    pub const ALL: [TimePeriod; 3] = [TimePeriod::Morning, TimePeriod::Day, TimePeriod::Night];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TimePeriod::Morning => "morning",
+            TimePeriod::Day => "day",
+            TimePeriod::Night => "night",
+        }
+    }
+
+}
+
+impl Season {
+
+    pub const ALL: [Season; 4] = [Season::Spring, Season::Summer, Season::Autumn, Season::Winter];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Season::Spring => "spring",
+            Season::Summer => "summer",
+            Season::Autumn => "autumn",
+            Season::Winter => "winter",
+        }
+    }
+
+}
+
+impl WorldExtensions {
+
+    pub fn map(&self, map_set: Option<&MapIdentifier>, map: &MapIdentifier) -> Option<&MapExtensions> {
+        match map_set {
+            Some(map_set) => self.map_sets.get(map_set).and_then(|maps| maps.get(map)),
+            None => self.chunks.get(map),
+        }
+    }
+
+}
+
+impl Weather {
+
+    const NAMED: [Weather; 15] = [
+        Weather::None,
+        Weather::SunnyClouds,
+        Weather::Sunny,
+        Weather::Rain,
+        Weather::Snow,
+        Weather::Thunderstorm,
+        Weather::FogHorizontal,
+        Weather::VolcanicAsh,
+        Weather::Sandstorm,
+        Weather::FogDiagonal,
+        Weather::Underwater,
+        Weather::Shade,
+        Weather::Drought,
+        Weather::Downpour,
+        Weather::UnderwaterBubbles,
+    ];
+
+    pub fn id(&self) -> u8 {
+        match self {
+            Weather::Other(id) => *id,
+            weather => Self::NAMED.iter().position(|named| named == weather).unwrap() as u8,
+        }
+    }
+
+}
+
+impl From<u8> for Weather {
+    fn from(id: u8) -> Self {
+        Self::NAMED.get(id as usize).copied().unwrap_or(Weather::Other(id))
+    }
+}
+
+impl MapType {
+
+    /// Map types with a name and their ids. Id 7 has no name.
+    const NAMED: [(u8, MapType); 9] = [
+        (0, MapType::None),
+        (1, MapType::Town),
+        (2, MapType::City),
+        (3, MapType::Route),
+        (4, MapType::Underground),
+        (5, MapType::Underwater),
+        (6, MapType::OceanRoute),
+        (8, MapType::Indoor),
+        (9, MapType::SecretBase),
+    ];
+
+    pub fn id(&self) -> u8 {
+        match self {
+            MapType::Other(id) => *id,
+            map_type => Self::NAMED.iter().find(|(_, named)| named == map_type).map(|(id, _)| *id).unwrap(),
+        }
+    }
+
+}
+
+impl From<u8> for MapType {
+    fn from(id: u8) -> Self {
+        Self::NAMED.iter().find(|(named, _)| *named == id).map(|(_, map_type)| *map_type).unwrap_or(MapType::Other(id))
+    }
+}
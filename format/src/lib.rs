@@ -0,0 +1,5 @@
+//! Reading and writing world files. The builder compiles maps into a [`output::CompiledWorld`],
+//! which the game reads back with [`output::read_world_file`].
+
+pub mod extension;
+pub mod output;
@@ -0,0 +1,275 @@
+use std::path::Path;
+
+use serde::{Serialize, Deserialize};
+
+use firecore_world_lib::serialized::SerializedWorld;
+
+use crate::extension::WorldExtensions;
+
+/// Bytes every world file starts with.
+pub const MAGIC: [u8; 4] = *b"FCWB";
+
+/// Version of the container layout. Bump this whenever the header or payload layout changes.
+///
+/// The payload is a [`CompiledWorld`], not a bare `SerializedWorld`, so world files are read through [`read_world`].
+pub const FORMAT_VERSION: u16 = 1;
+
+/// Version of this crate, which is released together with world-builder.
+pub const BUILDER_VERSION: &str = env!("CARGO_PKG_VERSION");
+/// The firecore-world-lib dependency this crate is built against, as given in its Cargo.toml.
+/// The dependency follows the repository's default branch, so change this together with it when it is pinned.
+pub const WORLD_LIB_VERSION: &str = "git+https://github.com/DoNotDoughnut/firecore-world-lib";
+
+/// Payload of a world file.
+///
+/// This is not a `SerializedWorld`: the world is followed by the data this builder adds to it, so readers
+/// need this crate (and not the builder) to decode a world file. The data for each map is kept in
+/// `extensions` under the same identifiers as the map, found with [`WorldExtensions::map`],
+/// as `WorldMap` has no place for it.
+#[derive(Serialize, Deserialize)]
+pub struct CompiledWorld {
+
+    pub world: SerializedWorld,
+    pub extensions: WorldExtensions,
+
+}
+
+/// Header written ahead of the serialized world.
+///
+/// On disk the magic bytes and format version come first as raw bytes (little endian),
+/// so a reader can reject a file before trying to decode the rest of the header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WorldHeader {
+
+    pub format_version: u16,
+    pub builder_version: String,
+    pub world_lib_version: String,
+    pub compression: Compression,
+    /// CRC32 of the uncompressed payload
+    pub checksum: u32,
+    /// Length of the uncompressed payload in bytes
+    pub length: u64,
+    /// Length of the payload in bytes as it is stored in the file
+    pub compressed_length: u64,
+
+}
+
+/// Codec applied to the serialized world before it is written.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Compression {
+    #[default]
+    None,
+    /// Deflate at the given level (0 - 10)
+    Deflate(u8),
+}
+
+#[derive(Serialize, Deserialize)]
+struct HeaderFields {
+    builder_version: String,
+    world_lib_version: String,
+    compression: Compression,
+    checksum: u32,
+    length: u64,
+    compressed_length: u64,
+}
+
+#[derive(Debug)]
+pub enum ReadError {
+    Io(std::io::Error),
+    Magic,
+    Version(u16),
+    Header(postcard::Error),
+    Length(u64, usize),
+    Checksum(u32, u32),
+    Decompress(String),
+    Payload(postcard::Error),
+}
+
+impl WorldHeader {
+
+    pub fn new(payload: &[u8], compression: Compression, compressed_length: usize) -> Self {
+        Self {
+            format_version: FORMAT_VERSION,
+            builder_version: BUILDER_VERSION.to_owned(),
+            world_lib_version: WORLD_LIB_VERSION.to_owned(),
+            compression,
+            checksum: crc32fast::hash(payload),
+            length: payload.len() as u64,
+            compressed_length: compressed_length as u64,
+        }
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>, postcard::Error> {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(&MAGIC);
+        bytes.extend_from_slice(&self.format_version.to_le_bytes());
+        bytes.extend(postcard::to_allocvec(&HeaderFields {
+            builder_version: self.builder_version.clone(),
+            world_lib_version: self.world_lib_version.clone(),
+            compression: self.compression,
+            checksum: self.checksum,
+            length: self.length,
+            compressed_length: self.compressed_length,
+        })?);
+        Ok(bytes)
+    }
+
+    /// Reads the header at the start of the given bytes, returning it along with the remaining bytes (the payload).
+    pub fn from_bytes(bytes: &[u8]) -> Result<(Self, &[u8]), ReadError> {
+        if bytes.len() < MAGIC.len() + 2 || bytes[..MAGIC.len()] != MAGIC {
+            return Err(ReadError::Magic);
+        }
+        let format_version = u16::from_le_bytes([bytes[MAGIC.len()], bytes[MAGIC.len() + 1]]);
+        if format_version != FORMAT_VERSION {
+            return Err(ReadError::Version(format_version));
+        }
+        let (fields, payload): (HeaderFields, &[u8]) = postcard::take_from_bytes(&bytes[MAGIC.len() + 2..]).map_err(ReadError::Header)?;
+        Ok((
+            Self {
+                format_version,
+                builder_version: fields.builder_version,
+                world_lib_version: fields.world_lib_version,
+                compression: fields.compression,
+                checksum: fields.checksum,
+                length: fields.length,
+                compressed_length: fields.compressed_length,
+            },
+            payload,
+        ))
+    }
+
+    /// Decompresses the stored payload and checks it against the lengths and checksum recorded in this header.
+    pub fn unpack(&self, stored: &[u8]) -> Result<Vec<u8>, ReadError> {
+        if self.compressed_length != stored.len() as u64 {
+            return Err(ReadError::Length(self.compressed_length, stored.len()));
+        }
+        let payload = self.compression.decompress(stored)?;
+        if self.length != payload.len() as u64 {
+            return Err(ReadError::Length(self.length, payload.len()));
+        }
+        let checksum = crc32fast::hash(&payload);
+        if self.checksum != checksum {
+            return Err(ReadError::Checksum(self.checksum, checksum));
+        }
+        Ok(payload)
+    }
+
+}
+
+impl Compression {
+
+    pub fn compress(&self, payload: &[u8]) -> Vec<u8> {
+        match self {
+            Compression::None => payload.to_vec(),
+            Compression::Deflate(level) => miniz_oxide::deflate::compress_to_vec(payload, *level),
+        }
+    }
+
+    pub fn decompress(&self, stored: &[u8]) -> Result<Vec<u8>, ReadError> {
+        match self {
+            Compression::None => Ok(stored.to_vec()),
+            Compression::Deflate(_) => miniz_oxide::inflate::decompress_to_vec(stored).map_err(|err| ReadError::Decompress(format!("{:?}", err))),
+        }
+    }
+
+}
+
+/// Serializes the world, compresses it and prepends a header to it.
+pub fn write_world(world: &CompiledWorld, compression: Compression) -> Result<(WorldHeader, Vec<u8>), postcard::Error> {
+    let payload = postcard::to_allocvec(world)?;
+    let stored = compression.compress(&payload);
+    let header = WorldHeader::new(&payload, compression, stored.len());
+    let mut bytes = header.to_bytes()?;
+    bytes.extend(stored);
+    Ok((header, bytes))
+}
+
+/// Validates the header of a world file and decodes the world after it.
+pub fn read_world(bytes: &[u8]) -> Result<(WorldHeader, CompiledWorld), ReadError> {
+    let (header, stored) = WorldHeader::from_bytes(bytes)?;
+    let payload = header.unpack(stored)?;
+    let world = postcard::from_bytes(&payload).map_err(ReadError::Payload)?;
+    Ok((header, world))
+}
+
+pub fn read_world_file<P: AsRef<Path>>(path: P) -> Result<(WorldHeader, CompiledWorld), ReadError> {
+    read_world(&std::fs::read(path).map_err(ReadError::Io)?)
+}
+
+impl std::fmt::Display for ReadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReadError::Io(err) => write!(f, "Could not read world file with error {}", err),
+            ReadError::Magic => write!(f, "File is not a world file (missing magic bytes)"),
+            ReadError::Version(version) => write!(f, "World file has format version {}, but this builder reads version {}", version, FORMAT_VERSION),
+            ReadError::Header(err) => write!(f, "Could not decode world file header with error {}", err),
+            ReadError::Length(expected, found) => write!(f, "World file payload should be {} bytes long but is {} bytes long", expected, found),
+            ReadError::Checksum(expected, found) => write!(f, "World file checksum mismatch (expected {:#010x}, found {:#010x})", expected, found),
+            ReadError::Decompress(err) => write!(f, "Could not decompress world file payload with error {}", err),
+            ReadError::Payload(err) => write!(f, "Could not decode world with error {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ReadError {}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn world_bytes(payload: &[u8], compression: Compression) -> (WorldHeader, Vec<u8>) {
+        let stored = compression.compress(payload);
+        let header = WorldHeader::new(payload, compression, stored.len());
+        let mut bytes = header.to_bytes().unwrap();
+        bytes.extend(stored);
+        (header, bytes)
+    }
+
+    fn payload() -> Vec<u8> {
+        b"pallet town ".repeat(16)
+    }
+
+    #[test]
+    fn header_round_trip() {
+        for compression in [Compression::None, Compression::Deflate(6)].iter().copied() {
+            let (header, bytes) = world_bytes(&payload(), compression);
+            let (read, stored) = WorldHeader::from_bytes(&bytes).unwrap();
+            assert_eq!(read, header);
+            assert_eq!(read.unpack(stored).unwrap(), payload());
+        }
+    }
+
+    #[test]
+    fn bad_magic() {
+        let (_, mut bytes) = world_bytes(&payload(), Compression::None);
+        bytes[0] ^= 0xFF;
+        assert!(matches!(WorldHeader::from_bytes(&bytes), Err(ReadError::Magic)));
+        assert!(matches!(WorldHeader::from_bytes(&MAGIC[..2]), Err(ReadError::Magic)));
+    }
+
+    #[test]
+    fn bad_version() {
+        let (_, mut bytes) = world_bytes(&payload(), Compression::None);
+        let version = FORMAT_VERSION + 1;
+        bytes[MAGIC.len()..MAGIC.len() + 2].copy_from_slice(&version.to_le_bytes());
+        assert!(matches!(WorldHeader::from_bytes(&bytes), Err(ReadError::Version(found)) if found == version));
+    }
+
+    #[test]
+    fn bad_length() {
+        let (_, mut bytes) = world_bytes(&payload(), Compression::None);
+        bytes.pop();
+        let (header, stored) = WorldHeader::from_bytes(&bytes).unwrap();
+        assert!(matches!(header.unpack(stored), Err(ReadError::Length(_, _))));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let (_, mut bytes) = world_bytes(&payload(), Compression::None);
+        *bytes.last_mut().unwrap() ^= 0xFF;
+        let (header, stored) = WorldHeader::from_bytes(&bytes).unwrap();
+        assert!(matches!(header.unpack(stored), Err(ReadError::Checksum(_, _))));
+    }
+
+}
@@ -0,0 +1,291 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+use std::path::Path;
+
+use ahash::AHashMap as HashMap;
+use serde::Serialize;
+
+use firecore_world_lib::TileId;
+use firecore_world_lib::map::{
+    WorldMap,
+    MapIdentifier,
+};
+
+use crate::extension::{EncounterKind, MapExtensions, TileAnimation};
+use crate::tileset::animation::SerializedTileAnimation;
+use crate::gba_map::{GbaMap, PaletteNaming, PaletteLayout, write_gba_map, unfix_tiles};
+use crate::output::{CompiledWorld, ReadError, read_world_file};
+use crate::world::{MapConfig, SerializedChunkMap, SerializedMapSet, SerializedMapSettings, SerializedWildEntry, WildEncounterType};
+
+pub fn decompile_file<P: AsRef<Path>>(world_file: P, output_dir: P) -> Result<(), ReadError> {
+    let (_, world) = read_world_file(world_file)?;
+    decompile(&world, output_dir);
+    Ok(())
+}
+
+/// Writes a compiled world back out as the directory layout `compile` reads,
+/// with maps under `maps/`, palettes under `textures/` and NPC types under `npcs/`.
+pub fn decompile<P: AsRef<Path>>(compiled: &CompiledWorld, output_dir: P) {
+
+    let world = &compiled.world;
+    let output_dir = output_dir.as_ref();
+    let maps = output_dir.join("maps");
+    let textures = output_dir.join("textures");
+    let npc_types = output_dir.join("npcs");
+
+    println!("Writing palettes...");
+
+    create_dir(&textures);
+
+    let mut palette_sizes = HashMap::new();
+
+    for palette in world.palettes.iter() {
+        let (width, height) = image::io::Reader::new(Cursor::new(&palette.bottom))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or_else(|| panic!("Could not read dimensions of palette #{}", palette.id));
+        palette_sizes.insert(palette.id, ((width >> 4) * (height >> 4)) as u16);
+        write(textures.join(PaletteNaming::default().bottom(palette.id)), &palette.bottom);
+        if let Some(top) = compiled.extensions.top_palettes.get(&palette.id) {
+            write(textures.join(PaletteNaming::default().top(palette.id)), top);
+        }
+    }
+
+    let palette_layout = PaletteLayout::new(palette_sizes);
+
+    if !compiled.extensions.animations.is_empty() {
+        write_animations(&textures.join("animations"), &palette_layout, &compiled.extensions.animations);
+    }
+
+    if !compiled.extensions.tile_behaviors.is_empty() {
+        let attributes = textures.join("attributes");
+        create_dir(&attributes);
+        for (palette, offset) in palette_layout.offsets.iter() {
+            let ranges = crate::tileset::attribute::attribute_ranges(&compiled.extensions.tile_behaviors, *offset, offset + palette_layout.sizes[palette]);
+            if !ranges.is_empty() {
+                write_ron(attributes.join(format!("{}.ron", palette)), &ranges);
+            }
+        }
+    }
+
+    println!("Writing chunks...");
+
+    for (index, chunk) in world.manager.chunk_map.chunks.iter() {
+        let chunk_dir = maps.join(index.to_string());
+        let config = write_map(&chunk_dir, &palette_layout, index, &chunk.map, compiled.extensions.map(None, index));
+        write_ron(
+            chunk_dir.join(format!("{}.ron", index)),
+            &SerializedChunkMap {
+                config,
+                coords: chunk.coords.clone(),
+                connections: chunk.connections.clone(),
+            }
+        );
+    }
+
+    println!("Writing map sets...");
+
+    for (set_index, map_set) in world.manager.map_set_manager.map_sets.iter() {
+        let set_dir = maps.join(set_index.to_string());
+        let mut dirs = Vec::with_capacity(map_set.maps.len());
+        for (index, map) in map_set.maps.iter() {
+            let config = write_map(&set_dir.join(index.to_string()), &palette_layout, index, map, compiled.extensions.map(Some(set_index), index));
+            write_ron(set_dir.join(index.to_string()).join(format!("{}.ron", index)), &config);
+            dirs.push(index.to_string());
+        }
+        dirs.sort();
+        write_ron(
+            set_dir.join(format!("{}.ron", set_index)),
+            &SerializedMapSet {
+                identifier: set_index.clone(),
+                dirs,
+            }
+        );
+    }
+
+    println!("Writing NPC types...");
+
+    for npc_type in world.npc_types.iter() {
+        let identifier = npc_type.config.identifier.to_string();
+        let npc_type_dir = npc_types.join(&identifier);
+        create_dir(&npc_type_dir);
+        write_ron(npc_type_dir.join(format!("{}.ron", identifier)), &npc_type.config);
+        write(npc_type_dir.join(identifier + ".png"), &npc_type.texture);
+        if let Some(battle_texture) = npc_type.battle_texture.as_ref() {
+            write(npc_type_dir.join("battle.png"), battle_texture);
+        }
+    }
+
+    println!("Finished decompiling world to {:?}", output_dir);
+
+}
+
+/// Writes the map file, warps, NPCs, scripts and wild encounters of a map into its directory and returns its configuration.
+fn write_map(map_dir: &Path, palette_layout: &PaletteLayout, identifier: &MapIdentifier, map: &WorldMap, extensions: Option<&MapExtensions>) -> MapConfig {
+
+    println!("    Writing map \"{}\"", map.name);
+
+    create_dir(map_dir);
+
+    let mut tiles = map.tiles.clone();
+    let mut borders: Vec<u16> = map.border.tiles.iter().copied().collect();
+    let (palettes, tilesets) = unfix_tiles(&mut tiles, &mut borders, palette_layout).unwrap_or_else(|err| panic!("Could not convert tiles of map {} with error {}", map.name, err));
+
+    let border_width = map.border.size;
+
+    if border_width == 0 || borders.is_empty() || borders.len() % border_width as usize != 0 || borders.len() / border_width as usize > u8::MAX as usize {
+        panic!("Map {} has a border of {} tiles, which is not a rectangle {} tiles wide", map.name, borders.len(), border_width);
+    }
+
+    let border_height = (borders.len() / border_width as usize) as u8;
+
+    let header = extensions.map(|extensions| extensions.header).unwrap_or_default();
+
+    let file = format!("{}.map", identifier);
+
+    write(map_dir.join(&file), &write_gba_map(&GbaMap {
+        music: map.music,
+        width: map.width,
+        height: map.height,
+        palettes,
+        weather: header.weather.id(),
+        map_type: header.map_type.id(),
+        show_name: header.show_name,
+        battle_scene: header.battle_scene,
+        border_width,
+        border_height,
+        borders,
+        tiles,
+        movements: map.movements.clone(),
+    }));
+
+    if !map.warps.is_empty() {
+        let warps = map_dir.join("warps");
+        create_dir(&warps);
+        for (index, warp) in map.warps.iter().enumerate() {
+            write_ron(warps.join(format!("{:03}.ron", index)), warp);
+        }
+    }
+
+    if !map.npc_manager.npcs.is_empty() {
+        let npcs = map_dir.join("npcs");
+        create_dir(&npcs);
+        for (index, npc) in map.npc_manager.npcs.iter() {
+            write_ron(npcs.join(format!("{}.ron", index)), &NPCFile { index, npc });
+        }
+    }
+
+    if !map.scripts.is_empty() {
+        let scripts = map_dir.join("scripts");
+        create_dir(&scripts);
+        for (index, script) in map.scripts.iter().enumerate() {
+            write_ron(scripts.join(format!("{:03}.ron", index)), script);
+        }
+    }
+
+    let wild_dir = map_dir.join("wild");
+    let mut encounter_tiles = HashMap::new();
+
+    if let Some(extensions) = extensions {
+        for (kind, entry) in extensions.encounters.iter() {
+            create_dir(&wild_dir);
+            write_toml(crate::world::wild::table_path(&wild_dir, *kind), &entry.table);
+            if let Some(tiles) = entry.tiles.as_ref() {
+                encounter_tiles.insert(*kind, tiles.clone());
+            }
+        }
+        for variant in extensions.variants.iter() {
+            create_dir(&wild_dir);
+            write_toml(crate::world::wild::variant_path(&wild_dir, variant), &variant.table);
+        }
+    }
+
+    let wild = match map.wild.as_ref() {
+        Some(wild) => {
+            create_dir(&wild_dir);
+            write_toml(crate::world::wild::table_path(&wild_dir, EncounterKind::Grass), &wild.table);
+            Some(SerializedWildEntry {
+                encounter_type: WildEncounterType::Original,
+                tiles: wild.tiles.clone(),
+                encounter_tiles,
+            })
+        }
+        None if !encounter_tiles.is_empty() => Some(SerializedWildEntry {
+            encounter_type: WildEncounterType::None,
+            tiles: None,
+            encounter_tiles,
+        }),
+        None => None,
+    };
+
+    MapConfig {
+        identifier: identifier.clone(),
+        name: map.name.clone(),
+        file,
+        extends: None,
+        settings: SerializedMapSettings::default(),
+        wild,
+        tilesets: Some(tilesets),
+        border: None,
+        music: None,
+    }
+
+}
+
+/// Writes each palette's animation file and frame strips, converting global tile ids back to ids within the palette.
+fn write_animations(animations_dir: &Path, palette_layout: &PaletteLayout, animations: &[TileAnimation]) {
+
+    create_dir(animations_dir);
+
+    let mut offsets: Vec<(u8, TileId)> = palette_layout.offsets.iter().map(|(palette, offset)| (*palette, *offset)).collect();
+    offsets.sort_by_key(|(_, offset)| *offset);
+
+    let mut palettes: BTreeMap<u8, Vec<SerializedTileAnimation>> = BTreeMap::new();
+
+    for animation in animations {
+        let (palette, offset) = offsets.iter().rev().find(|(_, offset)| *offset <= animation.tile).copied().unwrap_or_else(|| panic!("Animated tile #{} is not in any palette", animation.tile));
+        let tile = animation.tile - offset;
+        let frames = format!("{}_{}.png", palette, tile);
+        write(animations_dir.join(&frames), &animation.frames);
+        palettes.entry(palette).or_default().push(SerializedTileAnimation {
+            tile,
+            frames,
+            duration: animation.duration,
+        });
+    }
+
+    for (palette, animations) in palettes {
+        write_ron(animations_dir.join(format!("{}.ron", palette)), &animations);
+    }
+
+}
+
+/// Same layout as the `SerializedNPC` files the NPC loader reads.
+#[derive(Serialize)]
+pub(crate) struct NPCFile<'a, I, N> {
+    pub index: &'a I,
+    pub npc: &'a N,
+}
+
+pub(crate) fn create_dir(path: &Path) {
+    std::fs::create_dir_all(path).unwrap_or_else(|err| panic!("Could not create directory at {:?} with error {}", path, err));
+}
+
+pub(crate) fn write<P: AsRef<Path>>(path: P, bytes: &[u8]) {
+    let path = path.as_ref();
+    std::fs::write(path, bytes).unwrap_or_else(|err| panic!("Could not write file at {:?} with error {}", path, err));
+}
+
+pub(crate) fn write_ron<P: AsRef<Path>, T: Serialize>(path: P, value: &T) {
+    let path = path.as_ref();
+    let data = ron::ser::to_string_pretty(value, ron::ser::PrettyConfig::default()).unwrap_or_else(|err| panic!("Could not serialize {:?} with error {}", path, err));
+    write(path, data.as_bytes());
+}
+
+pub(crate) fn write_toml<P: AsRef<Path>, T: Serialize>(path: P, value: &T) {
+    let path = path.as_ref();
+    // Going through toml::Value lets toml order plain values ahead of tables
+    let data = toml::Value::try_from(value).and_then(|value| toml::to_string_pretty(&value)).unwrap_or_else(|err| panic!("Could not serialize {:?} with error {}", path, err));
+    write(path, data.as_bytes());
+}
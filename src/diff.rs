@@ -0,0 +1,419 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::hash::Hash;
+use std::path::Path;
+
+use ahash::AHashMap as HashMap;
+use serde::Serialize;
+
+use firecore_world_lib::map::{
+    WorldMap,
+    chunk::WorldChunk,
+    wild::WildEntry,
+};
+
+use crate::extension::{MapExtensions, EncounterKind, EncounterVariant};
+use crate::output::{CompiledWorld, ReadError, read_world_file};
+
+/// Semantic differences between two compiled worlds.
+pub struct WorldDiff {
+
+    pub chunks: Changes<MapDiff>,
+    pub map_sets: Changes<()>,
+    pub maps: Changes<MapDiff>,
+    pub palettes: Changes<()>,
+    pub npc_types: Changes<()>,
+
+}
+
+/// Entries (by name) that were added, removed or changed between two worlds.
+pub struct Changes<T> {
+
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+    pub changed: Vec<(String, T)>,
+
+}
+
+#[derive(Default)]
+pub struct MapDiff {
+
+    pub name: Option<(String, String)>,
+    pub music: Option<(u8, u8)>,
+    /// Old and new dimensions, if the map was resized
+    pub size: Option<((usize, usize), (usize, usize))>,
+    pub tiles: usize,
+    pub movements: usize,
+    pub border: bool,
+    /// Weather, map type, name popup or battle scene
+    pub header: bool,
+    pub coords: bool,
+    pub connections: bool,
+    pub warps: ListDiff,
+    pub npcs: ListDiff,
+    pub scripts: ListDiff,
+    pub wild: bool,
+
+}
+
+#[derive(Default)]
+pub struct ListDiff {
+
+    pub added: usize,
+    pub removed: usize,
+    pub changed: usize,
+
+}
+
+pub fn diff_files<P: AsRef<Path>>(old: P, new: P) -> Result<WorldDiff, ReadError> {
+    let (_, old) = read_world_file(old)?;
+    let (_, new) = read_world_file(new)?;
+    Ok(diff(&old, &new))
+}
+
+pub fn diff(old_compiled: &CompiledWorld, new_compiled: &CompiledWorld) -> WorldDiff {
+
+    let (old, new) = (&old_compiled.world, &new_compiled.world);
+    let (old_extensions, new_extensions) = (&old_compiled.extensions, &new_compiled.extensions);
+
+    let chunks = compare(
+        &old.manager.chunk_map.chunks,
+        &new.manager.chunk_map.chunks,
+        |index, old, new| diff_chunk(old, new, old_extensions.map(None, index), new_extensions.map(None, index)),
+    );
+
+    let map_sets = compare(
+        &old.manager.map_set_manager.map_sets,
+        &new.manager.map_set_manager.map_sets,
+        |_, _, _| None,
+    );
+
+    let mut all_maps = Changes::default();
+
+    for (set_index, old_set) in old.manager.map_set_manager.map_sets.iter() {
+        if let Some(new_set) = new.manager.map_set_manager.map_sets.get(set_index) {
+            let maps = compare(
+                &old_set.maps,
+                &new_set.maps,
+                |index, old, new| diff_map(old, new, old_extensions.map(Some(set_index), index), new_extensions.map(Some(set_index), index)),
+            );
+            let prefix = |index: String| format!("{}/{}", set_index, index);
+            all_maps.added.extend(maps.added.into_iter().map(prefix));
+            all_maps.removed.extend(maps.removed.into_iter().map(prefix));
+            all_maps.changed.extend(maps.changed.into_iter().map(|(index, map)| (prefix(index), map)));
+        }
+    }
+
+    all_maps.sort();
+
+    let palettes = compare(
+        &old.palettes.iter().map(|palette| (palette.id, palette)).collect(),
+        &new.palettes.iter().map(|palette| (palette.id, palette)).collect(),
+        |id, old, new| (!same(old, new) || !same(&old_extensions.top_palettes.get(id), &new_extensions.top_palettes.get(id))).then_some(()),
+    );
+
+    let npc_types = compare(
+        &old.npc_types.iter().map(|npc_type| (npc_type.config.identifier.to_string(), npc_type)).collect(),
+        &new.npc_types.iter().map(|npc_type| (npc_type.config.identifier.to_string(), npc_type)).collect(),
+        |_, old, new| (!same(old, new)).then_some(()),
+    );
+
+    WorldDiff {
+        chunks,
+        map_sets,
+        maps: all_maps,
+        palettes,
+        npc_types,
+    }
+
+}
+
+fn diff_chunk(old: &WorldChunk, new: &WorldChunk, old_extensions: Option<&MapExtensions>, new_extensions: Option<&MapExtensions>) -> Option<MapDiff> {
+    let mut diff = diff_map(&old.map, &new.map, old_extensions, new_extensions).unwrap_or_default();
+    diff.coords = !same(&old.coords, &new.coords);
+    diff.connections = !same(&old.connections, &new.connections);
+    (!diff.is_empty()).then_some(diff)
+}
+
+fn diff_map(old: &WorldMap, new: &WorldMap, old_extensions: Option<&MapExtensions>, new_extensions: Option<&MapExtensions>) -> Option<MapDiff> {
+
+    let mut diff = MapDiff::default();
+
+    if old.name != new.name {
+        diff.name = Some((old.name.clone(), new.name.clone()));
+    }
+
+    if old.music != new.music {
+        diff.music = Some((old.music, new.music));
+    }
+
+    if old.width != new.width || old.height != new.height {
+        diff.size = Some((
+            (old.width as usize, old.height as usize),
+            (new.width as usize, new.height as usize),
+        ));
+    }
+
+    diff.tiles = count_changes(old.width as usize, &old.tiles, new.width as usize, &new.tiles);
+    diff.movements = count_changes(old.width as usize, &old.movements, new.width as usize, &new.movements);
+    diff.border = !same(&old.border, &new.border);
+    diff.header = old_extensions.map(|extensions| extensions.header) != new_extensions.map(|extensions| extensions.header);
+    diff.warps = diff_list(&old.warps, &new.warps);
+    diff.npcs = diff_keyed(&old.npc_manager.npcs, &new.npc_manager.npcs);
+    diff.scripts = diff_list(&old.scripts, &new.scripts);
+    diff.wild = !same(&old.wild, &new.wild)
+        || !same(&encounters(old_extensions), &encounters(new_extensions))
+        || !same(variants(old_extensions), variants(new_extensions));
+
+    (!diff.is_empty()).then_some(diff)
+
+}
+
+/// Sorted so hash map ordering does not count as a change.
+fn encounters(extensions: Option<&MapExtensions>) -> BTreeMap<&EncounterKind, &WildEntry> {
+    extensions.map(|extensions| extensions.encounters.iter().collect()).unwrap_or_default()
+}
+
+fn variants(extensions: Option<&MapExtensions>) -> &[EncounterVariant] {
+    extensions.map(|extensions| &extensions.variants[..]).unwrap_or_default()
+}
+
+/// Counts the coordinates whose value changed, treating coordinates only present in one of the maps as changed.
+fn count_changes<T: PartialEq>(old_width: usize, old: &[T], new_width: usize, new: &[T]) -> usize {
+    if old_width == new_width {
+        old.iter().zip(new.iter()).filter(|(old, new)| old != new).count() + old.len().abs_diff(new.len())
+    } else {
+        let old_height = old.len() / old_width.max(1);
+        let new_height = new.len() / new_width.max(1);
+        let mut changes = 0;
+        for y in 0..old_height.max(new_height) {
+            for x in 0..old_width.max(new_width) {
+                let old = (x < old_width).then_some(old.get(x + y * old_width)).flatten();
+                let new = (x < new_width).then_some(new.get(x + y * new_width)).flatten();
+                if old != new {
+                    changes += 1;
+                }
+            }
+        }
+        changes
+    }
+}
+
+/// Compares two keyed collections, naming entries by their key.
+fn compare<K: Hash + Eq + ToString, V, T>(old: &HashMap<K, V>, new: &HashMap<K, V>, mut changed: impl FnMut(&K, &V, &V) -> Option<T>) -> Changes<T> {
+    let mut changes = Changes {
+        added: new.keys().filter(|key| !old.contains_key(*key)).map(ToString::to_string).collect(),
+        removed: old.keys().filter(|key| !new.contains_key(*key)).map(ToString::to_string).collect(),
+        changed: old.iter().filter_map(|(key, old)| new.get(key).and_then(|new| changed(key, old, new)).map(|diff| (key.to_string(), diff))).collect(),
+    };
+    changes.sort();
+    changes
+}
+
+/// Compares two lists of entries. An entry that is identical to any entry of the other list is unchanged.
+/// Entries that are not, at an index where the other list also has such an entry, count as changed, and the rest as removed or added.
+fn diff_list<T: Serialize>(old: &[T], new: &[T]) -> ListDiff {
+    let serialize = |entry: &T| postcard::to_allocvec(entry).ok();
+    let new_entries: Vec<Option<Vec<u8>>> = new.iter().map(serialize).collect();
+    let mut new_unmatched = vec![true; new.len()];
+    let mut old_unmatched = vec![false; old.len()];
+    for (index, entry) in old.iter().map(serialize).enumerate() {
+        match (0..new_entries.len()).find(|new| new_unmatched[*new] && new_entries[*new] == entry) {
+            Some(new) => new_unmatched[new] = false,
+            None => old_unmatched[index] = true,
+        }
+    }
+    let changed = old_unmatched.iter().zip(new_unmatched.iter()).filter(|(old, new)| **old && **new).count();
+    ListDiff {
+        added: new_unmatched.iter().filter(|unmatched| **unmatched).count() - changed,
+        removed: old_unmatched.iter().filter(|unmatched| **unmatched).count() - changed,
+        changed,
+    }
+}
+
+fn diff_keyed<K: Hash + Eq, V: Serialize>(old: &HashMap<K, V>, new: &HashMap<K, V>) -> ListDiff {
+    ListDiff {
+        added: new.keys().filter(|key| !old.contains_key(*key)).count(),
+        removed: old.keys().filter(|key| !new.contains_key(*key)).count(),
+        changed: old.iter().filter(|(key, old)| new.get(*key).map(|new| !same(*old, new)).unwrap_or_default()).count(),
+    }
+}
+
+/// Compares values by their serialized form, as world types do not implement PartialEq.
+pub(crate) fn same<T: Serialize + ?Sized>(a: &T, b: &T) -> bool {
+    postcard::to_allocvec(a).ok() == postcard::to_allocvec(b).ok()
+}
+
+impl WorldDiff {
+
+    pub fn is_empty(&self) -> bool {
+        self.chunks.is_empty() && self.map_sets.is_empty() && self.maps.is_empty() && self.palettes.is_empty() && self.npc_types.is_empty()
+    }
+
+}
+
+impl<T> Changes<T> {
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    fn sort(&mut self) {
+        self.added.sort();
+        self.removed.sort();
+        self.changed.sort_by(|(a, _), (b, _)| a.cmp(b));
+    }
+
+}
+
+impl<T> Default for Changes<T> {
+    fn default() -> Self {
+        Self {
+            added: Vec::new(),
+            removed: Vec::new(),
+            changed: Vec::new(),
+        }
+    }
+}
+
+impl MapDiff {
+
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.music.is_none() && self.size.is_none() && self.tiles == 0 && self.movements == 0 && !self.border && !self.header && !self.coords && !self.connections
+            && self.warps.is_empty() && self.npcs.is_empty() && self.scripts.is_empty() && !self.wild
+    }
+
+}
+
+impl ListDiff {
+
+    pub fn is_empty(&self) -> bool {
+        self.added == 0 && self.removed == 0 && self.changed == 0
+    }
+
+}
+
+impl Display for WorldDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.is_empty() {
+            return writeln!(f, "Worlds are identical");
+        }
+        self.chunks.write(f, "Chunks")?;
+        self.map_sets.write(f, "Map sets")?;
+        self.maps.write(f, "Map set maps")?;
+        self.palettes.write(f, "Palettes")?;
+        self.npc_types.write(f, "NPC types")
+    }
+}
+
+trait ChangeDisplay {
+    fn write(&self, f: &mut Formatter<'_>) -> FmtResult;
+}
+
+impl ChangeDisplay for () {
+    fn write(&self, _: &mut Formatter<'_>) -> FmtResult {
+        Ok(())
+    }
+}
+
+impl<T: ChangeDisplay> Changes<T> {
+
+    fn write(&self, f: &mut Formatter<'_>, title: &str) -> FmtResult {
+        if self.is_empty() {
+            return Ok(());
+        }
+        writeln!(f, "{}", title)?;
+        for added in self.added.iter() {
+            writeln!(f, "    + {}", added)?;
+        }
+        for removed in self.removed.iter() {
+            writeln!(f, "    - {}", removed)?;
+        }
+        for (changed, diff) in self.changed.iter() {
+            writeln!(f, "    ~ {}", changed)?;
+            diff.write(f)?;
+        }
+        Ok(())
+    }
+
+}
+
+impl ChangeDisplay for MapDiff {
+    fn write(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if let Some((old, new)) = &self.name {
+            writeln!(f, "        name: {} -> {}", old, new)?;
+        }
+        if let Some((old, new)) = self.music {
+            writeln!(f, "        music: {} -> {}", old, new)?;
+        }
+        if let Some(((old_width, old_height), (new_width, new_height))) = self.size {
+            writeln!(f, "        size: {}x{} -> {}x{}", old_width, old_height, new_width, new_height)?;
+        }
+        if self.tiles != 0 {
+            writeln!(f, "        {} tiles changed", self.tiles)?;
+        }
+        if self.movements != 0 {
+            writeln!(f, "        {} movement values changed", self.movements)?;
+        }
+        if self.border {
+            writeln!(f, "        border changed")?;
+        }
+        if self.header {
+            writeln!(f, "        header changed")?;
+        }
+        if self.coords {
+            writeln!(f, "        coordinates changed")?;
+        }
+        if self.connections {
+            writeln!(f, "        connections changed")?;
+        }
+        self.warps.write(f, "warps")?;
+        self.npcs.write(f, "NPCs")?;
+        self.scripts.write(f, "scripts")?;
+        if self.wild {
+            writeln!(f, "        wild encounters changed")?;
+        }
+        Ok(())
+    }
+}
+
+impl ListDiff {
+
+    fn write(&self, f: &mut Formatter<'_>, name: &str) -> FmtResult {
+        if !self.is_empty() {
+            writeln!(f, "        {}: {} added, {} removed, {} changed", name, self.added, self.removed, self.changed)?;
+        }
+        Ok(())
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn list(old: &[u8], new: &[u8]) -> (usize, usize, usize) {
+        let diff = diff_list(old, new);
+        (diff.added, diff.removed, diff.changed)
+    }
+
+    #[test]
+    fn reordered_entries_are_unchanged() {
+        assert_eq!(list(&[1, 2, 3], &[3, 1, 2]), (0, 0, 0));
+    }
+
+    #[test]
+    fn entries_at_the_same_index_are_changed() {
+        assert_eq!(list(&[1, 2, 3], &[1, 5, 3]), (0, 0, 1));
+        assert_eq!(list(&[1, 2], &[1, 5, 6]), (1, 0, 1));
+        assert_eq!(list(&[1, 2, 3], &[7]), (0, 2, 1));
+    }
+
+    #[test]
+    fn added_and_removed_entries() {
+        assert_eq!(list(&[1, 2], &[1, 2, 3]), (1, 0, 0));
+        assert_eq!(list(&[1, 2, 3], &[2]), (0, 2, 0));
+    }
+
+}
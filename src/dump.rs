@@ -0,0 +1,125 @@
+use std::collections::BTreeMap;
+use std::io::Cursor;
+
+use serde::Serialize;
+
+use ahash::AHashMap as HashMap;
+
+use firecore_world_lib::{
+    TileId,
+    map::{MapIdentifier, manager::WorldMapManager},
+    serialized::SerializedNPCTypeConfig,
+};
+
+use crate::extension::{MapExtensions, TileBehavior};
+use crate::output::CompiledWorld;
+
+/// Human readable format to dump a world in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    Ron,
+    Json,
+}
+
+impl DumpFormat {
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            DumpFormat::Ron => "ron",
+            DumpFormat::Json => "json",
+        }
+    }
+
+}
+
+/// Mirror of a serialized world with texture bytes replaced by a summary of them.
+#[derive(Serialize)]
+struct WorldDump<'a> {
+    manager: &'a WorldMapManager,
+    npc_types: Vec<NpcTypeDump<'a>>,
+    palettes: Vec<PaletteDump>,
+    extensions: ExtensionsDump<'a>,
+}
+
+#[derive(Serialize)]
+struct ExtensionsDump<'a> {
+    chunks: &'a HashMap<MapIdentifier, MapExtensions>,
+    map_sets: &'a HashMap<MapIdentifier, HashMap<MapIdentifier, MapExtensions>>,
+    animations: Vec<AnimationDump>,
+    tile_behaviors: BTreeMap<&'a TileId, &'a TileBehavior>,
+}
+
+#[derive(Serialize)]
+struct AnimationDump {
+    tile: TileId,
+    frames: TextureSummary,
+    duration: f32,
+}
+
+#[derive(Serialize)]
+struct NpcTypeDump<'a> {
+    config: &'a SerializedNPCTypeConfig,
+    texture: TextureSummary,
+    battle_texture: Option<TextureSummary>,
+}
+
+#[derive(Serialize)]
+struct PaletteDump {
+    id: u8,
+    bottom: TextureSummary,
+    top: Option<TextureSummary>,
+}
+
+#[derive(Serialize)]
+struct TextureSummary {
+    bytes: usize,
+    width: Option<u32>,
+    height: Option<u32>,
+}
+
+impl TextureSummary {
+
+    fn new(texture: &[u8]) -> Self {
+        let dimensions = image::io::Reader::new(Cursor::new(texture))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok());
+        Self {
+            bytes: texture.len(),
+            width: dimensions.map(|(width, _)| width),
+            height: dimensions.map(|(_, height)| height),
+        }
+    }
+
+}
+
+pub fn dump_world(compiled: &CompiledWorld, format: DumpFormat) -> Result<String, Box<dyn std::error::Error>> {
+    let world = &compiled.world;
+    let dump = WorldDump {
+        manager: &world.manager,
+        npc_types: world.npc_types.iter().map(|npc_type| NpcTypeDump {
+            config: &npc_type.config,
+            texture: TextureSummary::new(&npc_type.texture),
+            battle_texture: npc_type.battle_texture.as_deref().map(TextureSummary::new),
+        }).collect(),
+        palettes: world.palettes.iter().map(|palette| PaletteDump {
+            id: palette.id,
+            bottom: TextureSummary::new(&palette.bottom),
+            top: compiled.extensions.top_palettes.get(&palette.id).map(|top| TextureSummary::new(top)),
+        }).collect(),
+        extensions: ExtensionsDump {
+            chunks: &compiled.extensions.chunks,
+            map_sets: &compiled.extensions.map_sets,
+            animations: compiled.extensions.animations.iter().map(|animation| AnimationDump {
+                tile: animation.tile,
+                frames: TextureSummary::new(&animation.frames),
+                duration: animation.duration,
+            }).collect(),
+            tile_behaviors: compiled.extensions.tile_behaviors.iter().collect(),
+        },
+    };
+    Ok(match format {
+        DumpFormat::Ron => ron::ser::to_string_pretty(&dump, ron::ser::PrettyConfig::default())?,
+        DumpFormat::Json => serde_json::to_string_pretty(&dump)?,
+    })
+}
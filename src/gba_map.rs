@@ -1,4 +1,3 @@
-use std::ops::AddAssign;
 use std::path::Path;
 use std::path::PathBuf;
 
@@ -6,51 +5,94 @@ use ahash::AHashMap as HashMap;
 use firecore_world_lib::{MapSize, TileId, MovementId};
 use image::GenericImageView;
 
+use crate::world::SerializedTileset;
+
 pub struct GbaMap {
 	
 	pub music: u8,
 	pub width: MapSize,
 	pub height: MapSize,
 	pub palettes: [u8; 2],
-	pub borders: [TileId; 4],
+	pub weather: u8,
+	pub map_type: u8,
+	pub show_name: bool,
+	pub battle_scene: u8,
+	pub border_width: u8,
+	pub border_height: u8,
+	/// Border blocks, row by row
+	pub borders: Vec<TileId>,
 	pub tiles: Vec<TileId>,
 	pub movements: Vec<MovementId>,
 	
 }
 
-pub fn get_gba_map(file: Vec<u8>) -> GbaMap  {
+/// Border size of maps that do not store one in their header.
+pub const DEFAULT_BORDER_SIZE: (u8, u8) = (2, 2);
+
+const BORDER_OFFSET: usize = 52;
+
+/// Parses a gba map. The border size comes from `border_size` if given,
+/// otherwise from bytes 16 and 17 of the header, otherwise it is 2x2.
+pub fn get_gba_map(file: Vec<u8>, border_size: Option<(u8, u8)>) -> Result<GbaMap, String> {
 
 	let bytes = file;
 
+	if bytes.len() < BORDER_OFFSET {
+		return Err(format!("Map file is {} bytes, which is too short for its header", bytes.len()));
+	}
+
 	let music = bytes[40];
 
 	let width = bytes[0] as usize; // 0 - 3 reserved
 	let height = bytes[4] as usize; // 4 - 7 reserved
 	
 	let palettes = [bytes[8], bytes[12]]; // 8 - 11 reserved & 12 - 15 reserved
+
+	let (border_width, border_height) = border_size.unwrap_or_else(|| match (bytes[16], bytes[17]) {
+		(0, 0) => DEFAULT_BORDER_SIZE,
+		size => size,
+	});
+
+	if border_width == 0 || border_height == 0 {
+		return Err(format!("Border is {}x{} blocks, but it must be a rectangle of at least 1x1", border_width, border_height));
+	}
+
+	// 44 is the region map section, 45 the cave flag and 50 the floor number, which are not used
+	let weather = bytes[46];
+	let map_type = bytes[47];
+	let show_name = bytes[49] != 0;
+	let battle_scene = bytes[51];
+
+	let border_len = border_width as usize * border_height as usize;
+	let tiles_offset = BORDER_OFFSET + border_len * 2;
+
+	let size = width * height;
+
+	if bytes.len() < tiles_offset + size * 2 {
+		return Err(format!(
+			"Map file is {} bytes, but a {}x{} map with a {}x{} border needs {}",
+			bytes.len(), width, height, border_width, border_height, tiles_offset + size * 2
+		));
+	}
 	
-	//let show_name_on_entering = bytes[49];
-	
-	let mut borders: [u16; 4] = [0; 4];
+	let mut borders: Vec<TileId> = Vec::with_capacity(border_len);
 	
-	for x in 0..4 {
+	for x in 0..border_len {
 		
-		let location = 52+x*2;
+		let location = BORDER_OFFSET+x*2;
 		
 		let tile_num = (bytes[location+1]%4) as u16 * 256 + bytes[location] as u16;
 		
-		borders[x] = tile_num;
+		borders.push(tile_num);
 		
 	}
-
-	let size = width * height;
 	
 	let mut tiles: Vec<TileId> = Vec::with_capacity(size);
 	let mut movements: Vec<MovementId> = Vec::with_capacity(size);
 	
 	for tile in 0..size {
 
-		let location = 60 + tile * 2;
+		let location = tiles_offset + tile * 2;
 
 		let tile = (bytes[location+1]%4) as u16 * 256 + bytes[location] as u16;
 	
@@ -61,104 +103,537 @@ pub fn get_gba_map(file: Vec<u8>) -> GbaMap  {
 		
 	}
 
-	GbaMap {
-		
-		music: music,
-		width: width as MapSize,
-		height: height as MapSize,
-		palettes: palettes,
-		borders: borders,
-		tiles,
-		movements,
-		
-	}
+	Ok(
+		GbaMap {
+
+			music: music,
+			width: width as MapSize,
+			height: height as MapSize,
+			palettes: palettes,
+			weather,
+			map_type,
+			show_name,
+			battle_scene,
+			border_width,
+			border_height,
+			borders: borders,
+			tiles,
+			movements,
+
+		}
+	)
 	
 }
 
-pub fn fix_tiles(gba_map: &mut GbaMap, palette_sizes: &HashMap<u8, u16>) {
+/// Number of tiles a gba map can address, as tile ids are 10 bits.
+pub const LOCAL_TILES: u16 = 1024;
 
-	let offset = get_offset(gba_map, palette_sizes);
+/// Number of tiles in each palette and the global id of each palette's first tile, computed once for all maps.
+pub struct PaletteLayout {
 
-	let zero_size = *palette_sizes.get(&0).unwrap();
-	
-	for tile in gba_map.tiles.iter_mut() {
-		if *tile > zero_size {
-			tile.add_assign(offset);
-		}
-	}
+	pub sizes: HashMap<u8, u16>,
+	pub offsets: HashMap<u8, TileId>,
 
-	for index in 0..gba_map.borders.len() {
-		if gba_map.borders[index] > zero_size {
-			gba_map.borders[index] += offset;
+}
+
+impl PaletteLayout {
+
+	pub fn new(sizes: HashMap<u8, u16>) -> Self {
+		Self {
+			offsets: crate::tileset::palette_offsets(&sizes),
+			sizes,
 		}
 	}
 
-	if gba_map.palettes[0] > 0 {
-		let mut offset12: u16 = 0;
+}
+
+/// The tilesets of a map without explicit ones: its primary palette at 0 up to the size of palette #0
+/// (the size of every primary tileset), followed by as much of its secondary palette as a gba map can address.
+pub fn default_tilesets(gba_map: &GbaMap, layout: &PaletteLayout) -> Vec<SerializedTileset> {
+	let size = |palette: u8| layout.sizes.get(&palette).copied().unwrap_or_default();
+	let zero_size = size(0);
+	vec![
+		SerializedTileset {
+			palette: gba_map.palettes[0],
+			start: 0,
+			length: Some(size(gba_map.palettes[0]).min(zero_size)),
+		},
+		SerializedTileset {
+			palette: gba_map.palettes[1],
+			start: zero_size,
+			length: Some(size(gba_map.palettes[1]).min(LOCAL_TILES.saturating_sub(zero_size))),
+		},
+	]
+}
+
+/// Builds a table from each tile id in a gba map to its global tile id, checking that every tileset exists, fits and does not overlap another.
+pub fn tile_lookup(tilesets: &[SerializedTileset], layout: &PaletteLayout) -> Result<Vec<Option<TileId>>, String> {
+
+	let mut lookup = vec![None; LOCAL_TILES as usize];
+
+	for tileset in tilesets {
+
+		let (size, offset) = match (layout.sizes.get(&tileset.palette), layout.offsets.get(&tileset.palette)) {
+			(Some(size), Some(offset)) => (*size, *offset),
+			_ => return Err(format!("Palette #{} does not exist", tileset.palette)),
+		};
 
-		for x in 0..gba_map.palettes[0] {
-			offset12 += *palette_sizes.get(&x).unwrap();
+		let length = tileset.length.unwrap_or(size);
+
+		if length > size {
+			return Err(format!("Tileset of palette #{} has a length of {}, but the palette only has {} tiles", tileset.palette, length, size));
 		}
 
-		for tile in gba_map.tiles.iter_mut() {
-			if *tile < zero_size {
-				tile.add_assign(offset12);
-			}
+		if tileset.start as u32 + length as u32 > LOCAL_TILES as u32 {
+			return Err(format!("Tileset of palette #{} covers tiles {} to {}, past the {} tiles a gba map can address", tileset.palette, tileset.start, tileset.start as u32 + length as u32, LOCAL_TILES));
 		}
 
-		for index in 0..gba_map.borders.len() {
-			if gba_map.borders[index] < zero_size {
-				gba_map.borders[index] += offset12;
+		for local in 0..length {
+			let tile = &mut lookup[(tileset.start + local) as usize];
+			if tile.is_some() {
+				return Err(format!("Tileset of palette #{} overlaps another tileset at tile {}", tileset.palette, tileset.start + local));
 			}
+			*tile = Some(offset + local);
 		}
+
+	}
+
+	Ok(lookup)
+
+}
+
+/// Converts the tile ids of a gba map (map tiles and borders) to global tile ids using a table from `tile_lookup`.
+pub fn fix_tiles(gba_map: &mut GbaMap, lookup: &[Option<TileId>]) -> Result<(), String> {
+	for tile in gba_map.tiles.iter_mut().chain(gba_map.borders.iter_mut()) {
+		*tile = lookup.get(*tile as usize).copied().flatten().ok_or_else(|| format!("Tile {} is not in any of the map's tilesets", tile))?;
 	}
+	Ok(())
+}
+
+/// How palette images are named: `{prefix}{index}{bottom_suffix}.png`, such as `Palette12B.png`,
+/// with the optional top layer (drawn above characters) named `{prefix}{index}{top_suffix}.png`.
+#[derive(Debug, Clone)]
+pub struct PaletteNaming {
+
+	pub prefix: String,
+	pub bottom_suffix: String,
+	pub top_suffix: String,
 
 }
 
-pub fn get_offset(gba_map: &GbaMap, palette_sizes: &HashMap<u8, u16>) -> u16 { // To - do: change to recursive function
-	let mut offset = 0;
-	if gba_map.palettes[1] >= palette_sizes.len() as u8 {
-		eprintln!("Not enough palettes to support gba map textures. Need palette #{}", gba_map.palettes[1]);
-		return 0;
+impl Default for PaletteNaming {
+	fn default() -> Self {
+		Self {
+			prefix: String::from("Palette"),
+			bottom_suffix: String::from("B"),
+			top_suffix: String::from("T"),
+		}
 	}
-	for x in 1..gba_map.palettes[1] {
-		offset += palette_sizes.get(&x).unwrap();
+}
+
+impl PaletteNaming {
+
+	/// Returns the palette index in a filename following this pattern, or None if the filename does not follow it.
+	pub fn parse<'a>(&self, filename: &'a str, suffix: &str) -> Option<&'a str> {
+		filename
+			.strip_suffix(".png")
+			.and_then(|name| name.strip_suffix(suffix))
+			.and_then(|name| name.strip_prefix(self.prefix.as_str()))
 	}
-	return offset;
+
+	pub fn bottom(&self, index: u8) -> String {
+		format!("{}{}{}.png", self.prefix, index, self.bottom_suffix)
+	}
+
+	pub fn top(&self, index: u8) -> String {
+		format!("{}{}{}.png", self.prefix, index, self.top_suffix)
+	}
+
 }
 
-pub fn fill_palette_map<P: AsRef<Path>>(tile_textures: P) -> (HashMap<u8, u16>, HashMap<u8, Vec<u8>>) {
+/// Loads the palette images, returning the number of tiles in each palette, the bottom layer images and the top layer images.
+pub fn fill_palette_map<P: AsRef<Path>>(tile_textures: P, naming: &PaletteNaming) -> (HashMap<u8, u16>, HashMap<u8, Vec<u8>>, HashMap<u8, Vec<u8>>) {
+	let tile_textures = tile_textures.as_ref();
+
 	let mut sizes = HashMap::new();
 	let mut palettes = HashMap::new();
+	let mut tops = HashMap::new();
+	let mut dimensions: HashMap<u8, (u32, u32)> = HashMap::new();
+	let mut filenames: HashMap<u8, String> = HashMap::new();
+	let mut top_filenames: HashMap<u8, (String, PathBuf)> = HashMap::new();
+	let mut errors: u32 = 0;
 
-	if let Ok(dir) = std::fs::read_dir(tile_textures) {
-		let paths: Vec<PathBuf> = dir.filter(|entry| entry.is_ok()).map(|entry| entry.unwrap().path()).filter(|path| path.is_file()).collect();
-		for filepath in paths {
-			let filename = filepath.file_name().unwrap().to_string_lossy();
-			if filename.starts_with("P") {
-				if filename.ends_with("B.png") {
-					match filename[7..filename.len()-5].parse::<u8>() {
-						Ok(index) => {
-							match std::fs::read(&filepath) {
-							    Ok(bytes) => {
-									let img = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png).unwrap();
-									sizes.insert(index, ((img.width() >> 4) * (img.height() >> 4)) as u16);
-									palettes.insert(index, bytes);
-								}
-							    Err(err) => {
-									panic!("Could not read image at path {:?} with error {}", filepath, err);
-								}
-							}
-						}
-						Err(err) => {
-							panic!("Could not parse tile palette named {} with error {}", filename, err);
-						}
-					}
+	let dir = std::fs::read_dir(tile_textures).unwrap_or_else(|err| panic!("Could not read tile texture directory at {:?} with error {}", tile_textures, err));
+	let mut paths: Vec<PathBuf> = dir.filter(|entry| entry.is_ok()).map(|entry| entry.unwrap().path()).filter(|path| path.is_file()).collect();
+	paths.sort();
+
+	for filepath in paths {
+		let filename = filepath.file_name().unwrap().to_string_lossy().into_owned();
+
+		let (index, top) = match naming.parse(&filename, &naming.bottom_suffix) {
+			Some(index) => (index, false),
+			None => match naming.parse(&filename, &naming.top_suffix) {
+				Some(index) => (index, true),
+				None => {
+					eprintln!("Ignoring file {:?} in tile textures as it is not named like {} or {}", filepath, naming.bottom(0), naming.top(0));
+					continue;
 				}
 			}
+		};
+
+		let index = match index.parse::<u8>() {
+			Ok(index) => index,
+			Err(err) => {
+				eprintln!("Could not parse index of tile palette named {} with error {}", filename, err);
+				errors += 1;
+				continue;
+			}
+		};
+
+		// top layers are checked against their bottom layer once every bottom layer is loaded
+		if top {
+			if let Some((other, _)) = top_filenames.insert(index, (filename.clone(), filepath)) {
+				eprintln!("Top layer tile palettes {} and {} both have index {}", other, filename, index);
+				errors += 1;
+			}
+			continue;
+		}
+
+		if let Some(other) = filenames.insert(index, filename.clone()) {
+			eprintln!("Tile palettes {} and {} both have index {}", other, filename, index);
+			errors += 1;
+			continue;
+		}
+
+		let bytes = std::fs::read(&filepath).unwrap_or_else(|err| panic!("Could not read image at path {:?} with error {}", filepath, err));
+
+		let img = match image::load_from_memory_with_format(&bytes, image::ImageFormat::Png) {
+			Ok(img) => img,
+			Err(err) => {
+				eprintln!("Could not decode tile palette {} with error {}", filename, err);
+				errors += 1;
+				continue;
+			}
+		};
+
+		if img.width() == 0 || img.height() == 0 || img.width() % 16 != 0 || img.height() % 16 != 0 {
+			eprintln!("Tile palette {} is {}x{} pixels, but its dimensions must be non-zero multiples of 16", filename, img.width(), img.height());
+			errors += 1;
+			continue;
 		}
+
+		sizes.insert(index, ((img.width() >> 4) * (img.height() >> 4)) as u16);
+		dimensions.insert(index, img.dimensions());
+		palettes.insert(index, bytes);
 	}
 
-	(sizes, palettes)
+	for (index, (filename, filepath)) in top_filenames {
+		let dimensions = match dimensions.get(&index) {
+			Some(dimensions) => *dimensions,
+			None => {
+				eprintln!("Top layer tile palette {} has no matching bottom layer {}", filename, naming.bottom(index));
+				errors += 1;
+				continue;
+			}
+		};
+
+		let bytes = std::fs::read(&filepath).unwrap_or_else(|err| panic!("Could not read image at path {:?} with error {}", filepath, err));
+
+		match image::load_from_memory_with_format(&bytes, image::ImageFormat::Png) {
+			Ok(img) => {
+				if img.dimensions() != dimensions {
+					eprintln!(
+						"Top layer tile palette {} is {}x{} pixels, but its bottom layer is {}x{} pixels",
+						filename, img.width(), img.height(), dimensions.0, dimensions.1
+					);
+					errors += 1;
+					continue;
+				}
+				tops.insert(index, bytes);
+			}
+			Err(err) => {
+				eprintln!("Could not decode top layer tile palette {} with error {}", filename, err);
+				errors += 1;
+			}
+		}
+	}
 
-}
\ No newline at end of file
+	match filenames.keys().max() {
+		Some(max) => {
+			for index in 0..*max {
+				if !filenames.contains_key(&index) {
+					eprintln!("Missing tile palette {} (palette indices must be contiguous from 0)", naming.bottom(index));
+					errors += 1;
+				}
+			}
+		}
+		None => {
+			eprintln!("Could not find any tile palettes named like {}", naming.bottom(0));
+			errors += 1;
+		}
+	}
+
+	if errors != 0 {
+		panic!("Found {} errors in tile palettes at {:?}.", errors, tile_textures);
+	}
+
+	(sizes, palettes, tops)
+
+}
+
+
+/// Writes a gba map in the format read by `get_gba_map`.
+pub fn write_gba_map(gba_map: &GbaMap) -> Vec<u8> {
+
+	let tiles_offset = BORDER_OFFSET + gba_map.borders.len() * 2;
+
+	let mut bytes = vec![0; tiles_offset + gba_map.tiles.len() * 2];
+
+	bytes[0..4].copy_from_slice(&(gba_map.width as u32).to_le_bytes());
+	bytes[4..8].copy_from_slice(&(gba_map.height as u32).to_le_bytes());
+
+	bytes[8] = gba_map.palettes[0];
+	bytes[12] = gba_map.palettes[1];
+
+	if (gba_map.border_width, gba_map.border_height) != DEFAULT_BORDER_SIZE {
+		bytes[16] = gba_map.border_width;
+		bytes[17] = gba_map.border_height;
+	}
+
+	bytes[40] = gba_map.music;
+
+	bytes[46] = gba_map.weather;
+	bytes[47] = gba_map.map_type;
+	bytes[49] = gba_map.show_name as u8;
+	bytes[51] = gba_map.battle_scene;
+
+	for (x, border) in gba_map.borders.iter().enumerate() {
+		write_tile(&mut bytes, BORDER_OFFSET + x * 2, *border, 0);
+	}
+
+	for (index, (tile, movement)) in gba_map.tiles.iter().zip(gba_map.movements.iter()).enumerate() {
+		write_tile(&mut bytes, tiles_offset + index * 2, *tile, *movement);
+	}
+
+	bytes
+
+}
+
+fn write_tile(bytes: &mut [u8], location: usize, tile: TileId, movement: MovementId) {
+	bytes[location] = (tile % 256) as u8;
+	bytes[location + 1] = (movement << 2) | (tile / 256 % 4) as u8;
+}
+
+/// Reverses `fix_tiles`, turning global tile ids back into ids local to the map.
+///
+/// Each palette the map uses becomes a tileset, placed one after another in palette order and
+/// long enough to hold the highest tile the map uses from it.
+/// Returns the palettes to write in the map header and the tilesets.
+pub fn unfix_tiles(tiles: &mut [TileId], borders: &mut [TileId], layout: &PaletteLayout) -> Result<([u8; 2], Vec<SerializedTileset>), String> {
+
+	let mut starts: Vec<(u8, TileId)> = layout.offsets.iter().map(|(palette, offset)| (*palette, *offset)).collect();
+	starts.sort_by_key(|(_, offset)| *offset);
+
+	let palette_of = |tile: TileId| starts.iter().rev().find(|(_, offset)| *offset <= tile).copied().ok_or_else(|| format!("Tile #{} is not in any palette", tile));
+
+	// palette, offset and number of tiles used from the start of the palette
+	let mut used: Vec<(u8, TileId, u16)> = Vec::new();
+	for tile in tiles.iter().chain(borders.iter()) {
+		let (palette, offset) = palette_of(*tile)?;
+		let length = *tile - offset + 1;
+		match used.iter_mut().find(|(other, _, _)| *other == palette) {
+			Some((_, _, count)) => *count = (*count).max(length),
+			None => used.push((palette, offset, length)),
+		}
+	}
+	used.sort_unstable();
+
+	let mut tilesets = Vec::with_capacity(used.len());
+	let mut start: u32 = 0;
+	for (palette, _, length) in used.iter() {
+		tilesets.push(SerializedTileset {
+			palette: *palette,
+			start: start as u16,
+			length: Some(*length),
+		});
+		start += *length as u32;
+	}
+
+	if start > LOCAL_TILES as u32 {
+		return Err(format!("Map uses {} tiles from palettes {:?}, but a gba map can only address {}", start, used.iter().map(|(palette, _, _)| palette).collect::<Vec<_>>(), LOCAL_TILES));
+	}
+
+	for tile in tiles.iter_mut().chain(borders.iter_mut()) {
+		let (palette, offset) = palette_of(*tile)?;
+		let tileset = tilesets.iter().find(|tileset| tileset.palette == palette).unwrap();
+		*tile = *tile - offset + tileset.start;
+	}
+
+	let palettes = match tilesets.as_slice() {
+		[] => [0, 0],
+		[only] => [only.palette, only.palette],
+		[primary, .., secondary] => [primary.palette, secondary.palette],
+	};
+
+	Ok((palettes, tilesets))
+
+}
+
+#[cfg(test)]
+mod tests {
+
+	use super::*;
+
+	fn layout(sizes: &[(u8, u16)]) -> PaletteLayout {
+		PaletteLayout::new(sizes.iter().copied().collect())
+	}
+
+	fn gba_map(palettes: [u8; 2]) -> GbaMap {
+		GbaMap {
+			music: 0,
+			width: 0,
+			height: 0,
+			palettes,
+			weather: 0,
+			map_type: 0,
+			show_name: false,
+			battle_scene: 0,
+			border_width: DEFAULT_BORDER_SIZE.0,
+			border_height: DEFAULT_BORDER_SIZE.1,
+			borders: Vec::new(),
+			tiles: Vec::new(),
+			movements: Vec::new(),
+		}
+	}
+
+	fn tileset(palette: u8, start: u16, length: Option<u16>) -> SerializedTileset {
+		SerializedTileset {
+			palette,
+			start,
+			length,
+		}
+	}
+
+	#[test]
+	fn default_tilesets_fit_large_secondary_palette() {
+		let layout = layout(&[(0, 640), (1, 640), (7, 500)]);
+		let tilesets = default_tilesets(&gba_map([1, 7]), &layout);
+		assert_eq!(tilesets[0].length, Some(640));
+		assert_eq!(tilesets[1].start, 640);
+		assert_eq!(tilesets[1].length, Some(384));
+		let lookup = tile_lookup(&tilesets, &layout).unwrap();
+		assert_eq!(lookup[0], Some(640));
+		assert_eq!(lookup[640], Some(1280));
+		assert_eq!(lookup[1023], Some(1280 + 383));
+	}
+
+	#[test]
+	fn tile_lookup_rejects_bad_tilesets() {
+		let layout = layout(&[(0, 512), (1, 512), (2, 8)]);
+		assert!(tile_lookup(&[tileset(3, 0, None)], &layout).is_err());
+		assert!(tile_lookup(&[tileset(2, 0, Some(9))], &layout).is_err());
+		assert!(tile_lookup(&[tileset(0, 0, None), tileset(1, 513, None)], &layout).is_err());
+		assert!(tile_lookup(&[tileset(0, 0, None), tileset(2, 511, None)], &layout).is_err());
+		assert!(tile_lookup(&[tileset(0, 0, None), tileset(1, 512, None)], &layout).is_ok());
+	}
+
+	#[test]
+	fn unfix_tiles_reverses_tile_lookup() {
+		let layout = layout(&[(0, 4), (1, 3), (2, 5)]);
+		let global_tiles: Vec<TileId> = vec![0, 3, 7, 11, 8, 1];
+		let global_borders: Vec<TileId> = vec![7, 8, 9, 10];
+
+		let mut tiles = global_tiles.clone();
+		let mut borders = global_borders.clone();
+		let (palettes, tilesets) = unfix_tiles(&mut tiles, &mut borders, &layout).unwrap();
+
+		// palette #1 is unused, so palette #2 follows palette #0
+		assert_eq!(palettes, [0, 2]);
+		assert_eq!(tilesets.iter().map(|tileset| (tileset.palette, tileset.start, tileset.length)).collect::<Vec<_>>(), vec![(0, 0, Some(4)), (2, 4, Some(5))]);
+		assert!(tiles.iter().chain(borders.iter()).all(|tile| *tile < LOCAL_TILES));
+
+		let lookup = tile_lookup(&tilesets, &layout).unwrap();
+		let fixed = |local: &[TileId]| local.iter().map(|tile| lookup[*tile as usize].unwrap()).collect::<Vec<_>>();
+		assert_eq!(fixed(&tiles), global_tiles);
+		assert_eq!(fixed(&borders), global_borders);
+	}
+
+	#[test]
+	fn decompiles_large_secondary_palette() {
+		let layout = layout(&[(0, 640), (1, 640), (7, 500)]);
+		let mut map = gba_map([1, 7]);
+		let local_tiles: Vec<TileId> = vec![0, 639, 640, 1023, 700];
+		let local_borders: Vec<TileId> = vec![5, 641, 5, 1000];
+		map.tiles = local_tiles.clone();
+		map.borders = local_borders.clone();
+
+		let lookup = tile_lookup(&default_tilesets(&map, &layout), &layout).unwrap();
+		fix_tiles(&mut map, &lookup).unwrap();
+		let (global_tiles, global_borders) = (map.tiles.clone(), map.borders.clone());
+
+		let (palettes, tilesets) = unfix_tiles(&mut map.tiles, &mut map.borders, &layout).unwrap();
+		assert_eq!(palettes, [1, 7]);
+		assert_eq!(map.tiles, local_tiles);
+		assert_eq!(map.borders, local_borders);
+
+		let lookup = tile_lookup(&tilesets, &layout).unwrap();
+		fix_tiles(&mut map, &lookup).unwrap();
+		assert_eq!(map.tiles, global_tiles);
+		assert_eq!(map.borders, global_borders);
+	}
+
+	#[test]
+	fn unfix_tiles_rejects_too_many_tiles() {
+		let layout = layout(&[(0, 600), (1, 600)]);
+		assert!(unfix_tiles(&mut [599, 600 + 599], &mut [], &layout).is_err());
+	}
+
+	#[test]
+	fn palette_naming_parses_indices() {
+		let naming = PaletteNaming::default();
+		assert_eq!(naming.parse("Palette12B.png", &naming.bottom_suffix), Some("12"));
+		assert_eq!(naming.parse("Palette12T.png", &naming.top_suffix), Some("12"));
+		assert_eq!(naming.parse("Palette12T.png", &naming.bottom_suffix), None);
+		// too short to hold an index, which fill_palette_map reports as an error
+		assert_eq!(naming.parse("PaletteB.png", &naming.bottom_suffix), Some(""));
+		assert_eq!(naming.parse("B.png", &naming.bottom_suffix), None);
+		assert_eq!(naming.parse("Palette12.png", &naming.bottom_suffix), None);
+		assert_eq!(naming.parse("Palette12B", &naming.bottom_suffix), None);
+		assert_eq!(naming.parse(&naming.bottom(3), &naming.bottom_suffix), Some("3"));
+	}
+
+	/// Loads tile palettes with the given filenames, each a 16x16 pixel image, returning their sizes and top layers
+	/// or None if fill_palette_map finds errors.
+	fn load_palettes(test: &str, filenames: &[&str]) -> Option<(HashMap<u8, u16>, HashMap<u8, Vec<u8>>)> {
+		let dir = std::env::temp_dir().join(format!("world-builder-{}-{}", test, std::process::id()));
+		std::fs::create_dir_all(&dir).unwrap();
+		for filename in filenames {
+			image::RgbaImage::new(16, 16).save(dir.join(filename)).unwrap();
+		}
+		let result = std::panic::catch_unwind(|| fill_palette_map(&dir, &PaletteNaming::default()));
+		std::fs::remove_dir_all(&dir).unwrap();
+		result.ok().map(|(sizes, _, tops)| (sizes, tops))
+	}
+
+	#[test]
+	fn fill_palette_map_loads_palettes() {
+		let (sizes, tops) = load_palettes("palettes", &["Palette0B.png", "Palette1B.png", "Palette1T.png"]).unwrap();
+		assert_eq!(sizes.len(), 2);
+		assert_eq!(sizes.get(&1), Some(&1));
+		assert!(tops.contains_key(&1));
+	}
+
+	#[test]
+	fn fill_palette_map_rejects_duplicate_indices() {
+		assert!(load_palettes("duplicate-palettes", &["Palette0B.png", "Palette01B.png", "Palette1B.png"]).is_none());
+		assert!(load_palettes("duplicate-tops", &["Palette0B.png", "Palette0T.png", "Palette00T.png"]).is_none());
+	}
+
+	#[test]
+	fn fill_palette_map_rejects_gaps() {
+		assert!(load_palettes("palette-gap", &["Palette0B.png", "Palette2B.png"]).is_none());
+		assert!(load_palettes("top-without-bottom", &["Palette0B.png", "Palette1T.png"]).is_none());
+		assert!(load_palettes("no-index", &["Palette0B.png", "PaletteB.png"]).is_none());
+	}
+
+}
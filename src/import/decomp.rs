@@ -0,0 +1,928 @@
+//! Imports maps from the pokeemerald and pokefirered decompilation projects.
+//!
+//! Layouts (`layouts.json`, `map.bin` and `border.bin`), map headers, connections, warps, object events
+//! and wild encounters are converted, and the songs maps play become a music table. Object, coord and
+//! background events point at scripts written in assembly, so those script references are written to
+//! `unconverted/<map>.json` to be ported by hand.
+
+use std::collections::{BTreeMap, VecDeque};
+use std::path::Path;
+
+use ahash::AHashMap as HashMap;
+use serde::{Serialize, Deserialize};
+
+use firecore_util::{Coordinate, Direction, BoundingBox, Destination};
+use firecore_world_lib::{
+    MapSize,
+    map::{MapIdentifier, warp::{WarpEntry, WarpDestination}},
+    character::{Character, npc::{NPC, MovementType}},
+};
+
+use crate::decompile::{NPCFile, create_dir, write, write_ron, write_toml};
+use crate::extension::EncounterKind;
+use crate::gba_map::{GbaMap, write_gba_map};
+use crate::world::{MapConfig, SerializedChunkMap, SerializedMapSet, SerializedMapSettings, SerializedTileset, SerializedWildEntry, WildEncounterType};
+
+/// Options for importing a decomp project.
+#[derive(Debug, Clone, Default)]
+pub struct DecompImport {
+
+    /// Palette id of each tileset, by its label in `layouts.json` (such as `gTileset_General`)
+    pub tileset_palettes: HashMap<String, u8>,
+    /// NPC type of each object event graphics constant (such as `OBJ_EVENT_GFX_BOY_1`).
+    /// Object events with graphics that are not listed are skipped.
+    pub npc_types: HashMap<String, String>,
+
+}
+
+#[derive(Deserialize)]
+struct LayoutsFile {
+    layouts: Vec<LayoutJson>,
+}
+
+/// Layouts that are left out of a project are empty objects, so every field has a default.
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct LayoutJson {
+    id: String,
+    width: usize,
+    height: usize,
+    /// Only pokefirered stores the border size
+    border_width: Option<u8>,
+    border_height: Option<u8>,
+    primary_tileset: String,
+    secondary_tileset: String,
+    border_filepath: String,
+    blockdata_filepath: String,
+}
+
+#[derive(Deserialize)]
+struct MapJson {
+    id: String,
+    name: String,
+    layout: String,
+    #[serde(default)]
+    music: String,
+    #[serde(default)]
+    weather: String,
+    #[serde(default)]
+    map_type: String,
+    #[serde(default)]
+    show_map_name: bool,
+    #[serde(default)]
+    battle_scene: String,
+    #[serde(default)]
+    connections: Option<Vec<ConnectionJson>>,
+    #[serde(default)]
+    warp_events: Vec<WarpJson>,
+    #[serde(default)]
+    object_events: Vec<ObjectJson>,
+    #[serde(default)]
+    coord_events: Vec<EventJson>,
+    #[serde(default)]
+    bg_events: Vec<EventJson>,
+}
+
+#[derive(Deserialize)]
+struct ConnectionJson {
+    map: String,
+    offset: i32,
+    direction: String,
+}
+
+#[derive(Deserialize)]
+struct WarpJson {
+    x: i32,
+    y: i32,
+    dest_map: String,
+    /// A number, or a string in newer versions of the projects
+    dest_warp_id: serde_json::Value,
+}
+
+#[derive(Deserialize)]
+struct ObjectJson {
+    #[serde(default)]
+    graphics_id: String,
+    x: i32,
+    y: i32,
+    #[serde(default)]
+    movement_type: String,
+    #[serde(default)]
+    movement_range_y: u8,
+    /// A constant, or a number in older versions of the projects
+    #[serde(default)]
+    trainer_type: serde_json::Value,
+    #[serde(default)]
+    script: String,
+}
+
+/// A coord or background event. Only where it is and the script it runs are read.
+#[derive(Deserialize)]
+struct EventJson {
+    #[serde(rename = "type", default)]
+    kind: String,
+    x: i32,
+    y: i32,
+    #[serde(default)]
+    script: String,
+}
+
+#[derive(Deserialize)]
+struct WildFile {
+    wild_encounter_groups: Vec<WildGroup>,
+}
+
+#[derive(Deserialize)]
+struct WildGroup {
+    #[serde(default)]
+    for_maps: bool,
+    encounters: Vec<WildJson>,
+}
+
+#[derive(Deserialize)]
+struct WildJson {
+    map: Option<String>,
+    land_mons: Option<MonsJson>,
+    water_mons: Option<MonsJson>,
+    rock_smash_mons: Option<MonsJson>,
+    fishing_mons: Option<MonsJson>,
+}
+
+#[derive(Deserialize)]
+struct MonsJson {
+    encounter_rate: u8,
+    mons: Vec<MonJson>,
+}
+
+#[derive(Deserialize)]
+struct MonJson {
+    min_level: u8,
+    max_level: u8,
+    species: String,
+}
+
+/// Same layout as the wild pokemon table files.
+#[derive(Serialize)]
+struct TableFile {
+    encounter_ratio: u8,
+    encounter: Vec<SlotFile>,
+}
+
+#[derive(Serialize)]
+struct SlotFile {
+    pokemon_id: u16,
+    min_level: u8,
+    max_level: u8,
+}
+
+/// An event that runs an assembly script, which has to be ported by hand.
+#[derive(Serialize)]
+struct ScriptReference {
+    /// `object`, or the type of a coord or background event (such as `trigger` or `sign`)
+    event: String,
+    /// Index of the NPC an object event became
+    #[serde(skip_serializing_if = "Option::is_none")]
+    npc: Option<usize>,
+    x: i32,
+    y: i32,
+    script: String,
+}
+
+/// A map of the project and where it goes in the converted world.
+struct ImportedMap {
+    json: MapJson,
+    identifier: String,
+    /// The map set the map goes in, or `None` if it is a chunk
+    map_set: Option<String>,
+}
+
+/// Weather constants, in id order.
+const WEATHERS: [&str; 16] = [
+    "WEATHER_NONE",
+    "WEATHER_SUNNY_CLOUDS",
+    "WEATHER_SUNNY",
+    "WEATHER_RAIN",
+    "WEATHER_SNOW",
+    "WEATHER_RAIN_THUNDERSTORM",
+    "WEATHER_FOG_HORIZONTAL",
+    "WEATHER_VOLCANIC_ASH",
+    "WEATHER_SANDSTORM",
+    "WEATHER_FOG_DIAGONAL",
+    "WEATHER_UNDERWATER",
+    "WEATHER_SHADE",
+    "WEATHER_DROUGHT",
+    "WEATHER_DOWNPOUR",
+    "WEATHER_UNDERWATER_BUBBLES",
+    "WEATHER_ABNORMAL",
+];
+
+/// Map type constants, in id order.
+const MAP_TYPES: [&str; 10] = [
+    "MAP_TYPE_NONE",
+    "MAP_TYPE_TOWN",
+    "MAP_TYPE_CITY",
+    "MAP_TYPE_ROUTE",
+    "MAP_TYPE_UNDERGROUND",
+    "MAP_TYPE_UNDERWATER",
+    "MAP_TYPE_OCEAN_ROUTE",
+    "MAP_TYPE_UNKNOWN",
+    "MAP_TYPE_INDOOR",
+    "MAP_TYPE_SECRET_BASE",
+];
+
+/// Battle scene constants, in id order.
+const BATTLE_SCENES: [&str; 12] = [
+    "MAP_BATTLE_SCENE_NORMAL",
+    "MAP_BATTLE_SCENE_GYM",
+    "MAP_BATTLE_SCENE_MAGMA",
+    "MAP_BATTLE_SCENE_AQUA",
+    "MAP_BATTLE_SCENE_SIDNEY",
+    "MAP_BATTLE_SCENE_PHOEBE",
+    "MAP_BATTLE_SCENE_GLACIA",
+    "MAP_BATTLE_SCENE_DRAKE",
+    "MAP_BATTLE_SCENE_CHAMPION",
+    "MAP_BATTLE_SCENE_GROUDON",
+    "MAP_BATTLE_SCENE_KYOGRE",
+    "MAP_BATTLE_SCENE_RAYQUAZA",
+];
+
+/// Which decomp slot fills each of the 12 encounter slots, chosen so every species keeps the chance it has in the decomp.
+/// Surfing and rock smash tables have slots of 60, 30, 5, 4 and 1%.
+const WATER_SLOTS: [usize; 12] = [0, 0, 0, 0, 1, 1, 1, 1, 2, 3, 2, 4];
+/// Old rod slots are 70 and 30%.
+const OLD_ROD_SLOTS: [usize; 12] = [0, 0, 0, 0, 0, 1, 1, 1, 1, 1, 1, 1];
+/// Good rod slots are 60, 20 and 20%.
+const GOOD_ROD_SLOTS: [usize; 12] = [0, 0, 0, 0, 1, 1, 2, 2, 2, 2, 2, 2];
+/// Super rod slots are 40, 40, 15, 4 and 1%.
+const SUPER_ROD_SLOTS: [usize; 12] = [0, 0, 1, 1, 1, 1, 2, 2, 2, 3, 2, 4];
+/// Land slots already match the encounter slots.
+const LAND_SLOTS: [usize; 12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+/// Converts the maps of a decomp project into the layout `compile` reads, under `output_dir/maps`.
+///
+/// Maps with connections become chunks and every other map goes in a map set named after its map group.
+pub fn import_decomp<P: AsRef<Path>>(project: P, output_dir: P, options: &DecompImport) {
+
+    let project = project.as_ref();
+    let output_dir = output_dir.as_ref();
+    let maps_dir = output_dir.join("maps");
+
+    let mut errors: u32 = 0;
+
+    println!("Reading layouts...");
+
+    let layouts_file = project.join("data/layouts/layouts.json");
+    let layouts: LayoutsFile = read_json(&layouts_file);
+    let layouts: HashMap<String, LayoutJson> = layouts.layouts.into_iter().filter(|layout| !layout.id.is_empty()).map(|layout| (layout.id.clone(), layout)).collect();
+
+    println!("Reading maps...");
+
+    let groups: serde_json::Value = read_json(&project.join("data/maps/map_groups.json"));
+    let group_order: Vec<String> = serde_json::from_value(groups["group_order"].clone()).unwrap_or_else(|err| panic!("Could not read group order of map groups with error {}", err));
+
+    let mut maps = Vec::new();
+
+    for group in group_order {
+        let dirs: Vec<String> = serde_json::from_value(groups[group.as_str()].clone()).unwrap_or_else(|err| panic!("Could not read maps of map group {} with error {}", group, err));
+        let map_set = short_identifier(snake_case(group.trim_start_matches("gMapGroup_")));
+        for dir in dirs {
+            let json: MapJson = read_json(&project.join("data/maps").join(&dir).join("map.json"));
+            maps.push(ImportedMap {
+                identifier: short_identifier(snake_case(&json.name)),
+                map_set: Some(map_set.clone()),
+                json,
+            });
+        }
+    }
+
+    // maps with connections (or that others connect to) are chunks
+    let connected: Vec<String> = maps.iter().flat_map(|map| map.json.connections.iter().flatten().map(|connection| connection.map.clone())).collect();
+    for map in maps.iter_mut() {
+        if map.json.connections.as_ref().map(|connections| !connections.is_empty()).unwrap_or_default() || connected.contains(&map.json.id) {
+            map.map_set = None;
+        }
+    }
+
+    let by_id: HashMap<&str, &ImportedMap> = maps.iter().map(|map| (map.json.id.as_str(), map)).collect();
+
+    let coords = chunk_coordinates(&maps, &by_id, &layouts);
+
+    let species = read_species(&project.join("include/constants/pokedex.h"));
+
+    let music = music_table(&read_songs(&project.join("include/constants/songs.h")), &maps);
+
+    let mut wild: HashMap<String, WildJson> = HashMap::new();
+    let wild_file = project.join("src/data/wild_encounters.json");
+    if wild_file.is_file() {
+        let file: WildFile = read_json(&wild_file);
+        for group in file.wild_encounter_groups.into_iter().filter(|group| group.for_maps) {
+            for encounter in group.encounters {
+                if let Some(map) = encounter.map.clone() {
+                    // pokefirered lists FireRed and LeafGreen tables for the same map; the first one is used
+                    wild.entry(map).or_insert(encounter);
+                }
+            }
+        }
+    }
+
+    let mut map_sets: BTreeMap<&str, Vec<String>> = BTreeMap::new();
+    let mut unconverted = 0;
+
+    for map in maps.iter() {
+
+        println!("    Importing map {}", map.json.name);
+
+        let map_dir = match map.map_set.as_ref() {
+            Some(map_set) => {
+                map_sets.entry(map_set).or_default().push(map.identifier.clone());
+                maps_dir.join(map_set).join(&map.identifier)
+            }
+            None => maps_dir.join(&map.identifier),
+        };
+
+        create_dir(&map_dir);
+
+        let layout = match layouts.get(&map.json.layout) {
+            Some(layout) => layout,
+            None => {
+                eprintln!("Map {} uses layout {}, which is not in {:?}", map.json.name, map.json.layout, layouts_file);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let gba_map = match read_layout(project, layout, map, &music, options) {
+            Ok(gba_map) => gba_map,
+            Err(err) => {
+                eprintln!("Could not convert layout {} of map {} with error {}", layout.id, map.json.name, err);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let file = format!("{}.map", map.identifier);
+        write(map_dir.join(&file), &write_gba_map(&gba_map));
+
+        errors += write_warps(&map_dir, map, &by_id);
+
+        let wild_entry = match wild.get(&map.json.id) {
+            Some(tables) => match write_wild(&map_dir.join("wild"), tables, &species) {
+                Ok(wild_entry) => wild_entry,
+                Err(err) => {
+                    eprintln!("Could not convert wild encounters of map {} with error {}", map.json.name, err);
+                    errors += 1;
+                    None
+                }
+            },
+            None => None,
+        };
+
+        errors += write_npcs(&map_dir, map, options);
+
+        let scripts = script_references(map);
+        if !scripts.is_empty() {
+            let unconverted_dir = output_dir.join("unconverted");
+            create_dir(&unconverted_dir);
+            let data = serde_json::to_string_pretty(&scripts).unwrap_or_else(|err| panic!("Could not serialize scripts of map {} with error {}", map.json.name, err));
+            write(unconverted_dir.join(format!("{}.json", map.identifier)), data.as_bytes());
+            unconverted += 1;
+        }
+
+        let config = MapConfig {
+            identifier: identifier(&map.identifier),
+            name: map.json.name.clone(),
+            file,
+            extends: None,
+            settings: SerializedMapSettings::default(),
+            wild: wild_entry,
+            // decomp maps use the first tiles for the primary tileset and the rest for the secondary tileset
+            tilesets: Some(vec![
+                SerializedTileset {
+                    palette: gba_map.palettes[0],
+                    start: 0,
+                    length: None,
+                },
+                SerializedTileset {
+                    palette: gba_map.palettes[1],
+                    start: primary_tiles(layout),
+                    length: None,
+                },
+            ]),
+            border: None,
+            // the track is in the map file, as an id from the music table written below
+            music: None,
+        };
+
+        match map.map_set {
+            Some(_) => write_ron(map_dir.join(format!("{}.ron", map.identifier)), &config),
+            None => {
+                let connections: Vec<MapIdentifier> = map.json.connections.iter().flatten()
+                    .filter(|connection| offset_direction(&connection.direction).is_some())
+                    .filter_map(|connection| by_id.get(connection.map.as_str()))
+                    .map(|other| identifier(&other.identifier))
+                    .collect();
+                let (x, y) = coords.get(map.json.id.as_str()).copied().unwrap_or_default();
+                write_ron(
+                    map_dir.join(format!("{}.ron", map.identifier)),
+                    &SerializedChunkMap {
+                        config,
+                        coords: Coordinate::new(x as _, y as _),
+                        connections: connections.into_iter().collect(),
+                    }
+                );
+            }
+        }
+
+    }
+
+    for (map_set, dirs) in map_sets {
+        write_ron(
+            maps_dir.join(map_set).join(format!("{}.ron", map_set)),
+            &SerializedMapSet {
+                identifier: identifier(map_set),
+                dirs,
+            }
+        );
+    }
+
+    let music_file = output_dir.join("music.toml");
+    write_toml(&music_file, &music.iter().map(|(song, id)| (track_name(song), *id)).collect::<BTreeMap<String, u8>>());
+
+    if errors != 0 {
+        panic!("Found {} errors importing decomp project at {:?}.", errors, project);
+    }
+
+    if unconverted != 0 {
+        println!("Wrote the scripts of {} maps to {:?} to be ported by hand", unconverted, output_dir.join("unconverted"));
+    }
+
+    println!("Wrote music table of {} tracks to {:?}, which the music_table compile option should point at", music.len(), music_file);
+
+    println!("Finished importing {} maps to {:?}", maps.len(), output_dir);
+
+}
+
+/// Reads the blockdata and border of a layout. Blocks are stored the same way as in gba map files.
+fn read_layout(project: &Path, layout: &LayoutJson, map: &ImportedMap, music: &HashMap<String, u8>, options: &DecompImport) -> Result<GbaMap, String> {
+
+    let palette = |tileset: &str| options.tileset_palettes.get(tileset).copied().ok_or_else(|| format!("tileset {} has no palette id", tileset));
+    let palettes = [palette(&layout.primary_tileset)?, palette(&layout.secondary_tileset)?];
+
+    let border_width = layout.border_width.unwrap_or(2);
+    let border_height = layout.border_height.unwrap_or(2);
+
+    let blocks = read_blocks(&project.join(&layout.blockdata_filepath), layout.width * layout.height)?;
+    let borders = read_blocks(&project.join(&layout.border_filepath), border_width as usize * border_height as usize)?;
+
+    Ok(GbaMap {
+        music: music.get(&map.json.music).copied().unwrap_or(SILENCE),
+        width: layout.width as MapSize,
+        height: layout.height as MapSize,
+        palettes,
+        weather: constant_id(&map.json.weather, &WEATHERS, "weather", &map.json.name),
+        map_type: constant_id(&map.json.map_type, &MAP_TYPES, "map type", &map.json.name),
+        show_name: map.json.show_map_name,
+        battle_scene: constant_id(&map.json.battle_scene, &BATTLE_SCENES, "battle scene", &map.json.name),
+        border_width,
+        border_height,
+        borders: borders.iter().map(|(tile, _)| *tile).collect(),
+        tiles: blocks.iter().map(|(tile, _)| *tile).collect(),
+        movements: blocks.iter().map(|(_, movement)| *movement).collect(),
+    })
+
+}
+
+/// Reads `len` blocks, each a 10 bit metatile id followed by 6 bits of collision and elevation.
+fn read_blocks(path: &Path, len: usize) -> Result<Vec<(u16, u8)>, String> {
+    let bytes = std::fs::read(path).map_err(|err| format!("could not read {:?} ({})", path, err))?;
+    if bytes.len() != len * 2 {
+        return Err(format!("{:?} is {} bytes, but should be {} bytes", path, bytes.len(), len * 2));
+    }
+    Ok(bytes.chunks_exact(2).map(|block| {
+        let block = u16::from_le_bytes([block[0], block[1]]);
+        (block & 0x3FF, (block >> 10) as u8)
+    }).collect())
+}
+
+/// Primary tilesets have 640 metatiles in pokefirered, which stores border sizes, and 512 in pokeemerald.
+fn primary_tiles(layout: &LayoutJson) -> u16 {
+    match layout.border_width.is_some() {
+        true => 640,
+        false => 512,
+    }
+}
+
+fn write_warps(map_dir: &Path, map: &ImportedMap, by_id: &HashMap<&str, &ImportedMap>) -> u32 {
+
+    let mut errors: u32 = 0;
+    let warps_dir = map_dir.join("warps");
+
+    for (index, warp) in map.json.warp_events.iter().enumerate() {
+
+        let destination = match by_id.get(warp.dest_map.as_str()) {
+            Some(destination) => destination,
+            None => {
+                // MAP_DYNAMIC and similar depend on game state
+                eprintln!("Skipping warp #{} of map {} to {}, which is not a map", index, map.json.name, warp.dest_map);
+                continue;
+            }
+        };
+
+        let dest_warp = match warp.dest_warp_id.as_u64().or_else(|| warp.dest_warp_id.as_str().and_then(|id| id.parse().ok())) {
+            Some(dest_warp) => dest_warp as usize,
+            None => {
+                eprintln!("Skipping warp #{} of map {} to warp {} of {}, which is not a warp number", index, map.json.name, warp.dest_warp_id, warp.dest_map);
+                continue;
+            }
+        };
+
+        let position = match destination.json.warp_events.get(dest_warp) {
+            Some(position) => position,
+            None => {
+                eprintln!("Warp #{} of map {} goes to warp #{} of {}, which does not exist", index, map.json.name, dest_warp, destination.json.name);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let entry = WarpEntry {
+            location: BoundingBox {
+                min: Coordinate::new(warp.x as _, warp.y as _),
+                max: Coordinate::new(warp.x as _, warp.y as _),
+            },
+            destination: WarpDestination {
+                map: destination.map_set.as_deref().map(identifier),
+                index: identifier(&destination.identifier),
+                position: Destination {
+                    coords: Coordinate::new(position.x as _, position.y as _),
+                    direction: None,
+                },
+            },
+        };
+
+        create_dir(&warps_dir);
+        write_ron(warps_dir.join(format!("{:03}.ron", index)), &entry);
+
+    }
+
+    errors
+
+}
+
+/// Writes an NPC file for each object event whose graphics have an NPC type. Trainer data is not converted.
+fn write_npcs(map_dir: &Path, map: &ImportedMap, options: &DecompImport) -> u32 {
+
+    let mut errors: u32 = 0;
+    let npcs_dir = map_dir.join("npcs");
+
+    for (index, object) in map.json.object_events.iter().enumerate() {
+
+        let npc_type = match options.npc_types.get(&object.graphics_id) {
+            Some(npc_type) => npc_type,
+            None => {
+                eprintln!("Skipping object event #{} of map {}, as graphics {} have no NPC type", index, map.json.name, object.graphics_id);
+                continue;
+            }
+        };
+
+        let npc_type = match npc_type.parse() {
+            Ok(npc_type) => npc_type,
+            Err(_) => {
+                eprintln!("Could not use \"{}\", the NPC type of graphics {}, as an NPC type identifier", npc_type, object.graphics_id);
+                errors += 1;
+                continue;
+            }
+        };
+
+        if is_trainer(&object.trainer_type) {
+            eprintln!("Object event #{} of map {} is a trainer, whose party and sight range are not converted", index, map.json.name);
+        }
+
+        let (direction, movement) = movement(&object.movement_type, object.movement_range_y);
+
+        let npc = NPC {
+            name: snake_case(object.graphics_id.trim_start_matches("OBJ_EVENT_GFX_")),
+            npc_type,
+            character: Character {
+                position: Coordinate::new(object.x as _, object.y as _).position(direction),
+                ..Default::default()
+            },
+            movement,
+            trainer: None,
+        };
+
+        create_dir(&npcs_dir);
+        write_ron(npcs_dir.join(format!("{:03}.ron", index)), &NPCFile { index: &(index as u8), npc: &npc });
+
+    }
+
+    errors
+
+}
+
+/// The direction an object event faces and how it moves. Movement NPCs cannot do becomes looking around.
+fn movement(movement_type: &str, range: u8) -> (Direction, MovementType) {
+    match movement_type {
+        "MOVEMENT_TYPE_NONE" | "MOVEMENT_TYPE_FACE_DOWN" => (Direction::Down, MovementType::Still),
+        "MOVEMENT_TYPE_FACE_UP" => (Direction::Up, MovementType::Still),
+        "MOVEMENT_TYPE_FACE_LEFT" => (Direction::Left, MovementType::Still),
+        "MOVEMENT_TYPE_FACE_RIGHT" => (Direction::Right, MovementType::Still),
+        "MOVEMENT_TYPE_WALK_UP_AND_DOWN" => (Direction::Down, MovementType::WalkUpAndDown(range)),
+        _ => (Direction::Down, MovementType::LookAround),
+    }
+}
+
+fn is_trainer(trainer_type: &serde_json::Value) -> bool {
+    match trainer_type {
+        serde_json::Value::Null => false,
+        serde_json::Value::Number(number) => number.as_u64() != Some(0),
+        serde_json::Value::String(constant) => constant != "TRAINER_TYPE_NONE" && constant != "0",
+        _ => true,
+    }
+}
+
+/// The assembly scripts the events of a map run.
+fn script_references(map: &ImportedMap) -> Vec<ScriptReference> {
+    // maps with no script for an event use 0 or NULL
+    let has_script = |script: &str| !script.is_empty() && script != "0" && script != "0x0" && script != "NULL";
+    let objects = map.json.object_events.iter().enumerate()
+        .filter(|(_, object)| has_script(&object.script))
+        .map(|(index, object)| ScriptReference {
+            event: String::from("object"),
+            npc: Some(index),
+            x: object.x,
+            y: object.y,
+            script: object.script.clone(),
+        });
+    let events = map.json.coord_events.iter().chain(map.json.bg_events.iter())
+        .filter(|event| has_script(&event.script))
+        .map(|event| ScriptReference {
+            event: event.kind.clone(),
+            npc: None,
+            x: event.x,
+            y: event.y,
+            script: event.script.clone(),
+        });
+    objects.chain(events).collect()
+}
+
+/// Writes the tables of a map under its wild directory and returns its wild entry.
+fn write_wild(wild_dir: &Path, tables: &WildJson, species: &HashMap<String, u16>) -> Result<Option<SerializedWildEntry>, String> {
+
+    let mut written = Vec::new();
+
+    if let Some(land) = tables.land_mons.as_ref() {
+        written.push((EncounterKind::Grass, table(land, 0..12, &LAND_SLOTS, species)?));
+    }
+    if let Some(water) = tables.water_mons.as_ref() {
+        written.push((EncounterKind::Surf, table(water, 0..5, &WATER_SLOTS, species)?));
+    }
+    if let Some(rock_smash) = tables.rock_smash_mons.as_ref() {
+        written.push((EncounterKind::RockSmash, table(rock_smash, 0..5, &WATER_SLOTS, species)?));
+    }
+    if let Some(fishing) = tables.fishing_mons.as_ref() {
+        written.push((EncounterKind::OldRod, table(fishing, 0..2, &OLD_ROD_SLOTS, species)?));
+        written.push((EncounterKind::GoodRod, table(fishing, 2..5, &GOOD_ROD_SLOTS, species)?));
+        written.push((EncounterKind::SuperRod, table(fishing, 5..10, &SUPER_ROD_SLOTS, species)?));
+    }
+
+    if written.is_empty() {
+        return Ok(None);
+    }
+
+    create_dir(wild_dir);
+
+    for (kind, table) in written.iter() {
+        write_toml(crate::world::wild::table_path(wild_dir, *kind), table);
+    }
+
+    Ok(Some(SerializedWildEntry {
+        encounter_type: match tables.land_mons.is_some() {
+            true => WildEncounterType::Original,
+            false => WildEncounterType::None,
+        },
+        tiles: None,
+        encounter_tiles: HashMap::new(),
+    }))
+
+}
+
+/// Spreads the decomp slots in `range` over the 12 encounter slots.
+fn table(mons: &MonsJson, range: std::ops::Range<usize>, slots: &[usize; 12], species: &HashMap<String, u16>) -> Result<TableFile, String> {
+    let encounter_ratio = mons.encounter_rate;
+    let mons = mons.mons.get(range.clone()).ok_or_else(|| format!("expected slots {:?}, but there are {} slots", range, mons.mons.len()))?;
+    let mut encounter = Vec::with_capacity(slots.len());
+    for slot in slots.iter() {
+        let mon = &mons[*slot];
+        encounter.push(SlotFile {
+            pokemon_id: *species.get(&mon.species).ok_or_else(|| format!("unknown species {}", mon.species))?,
+            min_level: mon.min_level,
+            max_level: mon.max_level,
+        });
+    }
+    Ok(TableFile {
+        encounter_ratio,
+        encounter,
+    })
+}
+
+/// Music id of maps whose song is not in `songs.h`.
+const SILENCE: u8 = 0;
+
+/// Reads the `MUS_*` song constants, sorted by their id in the project.
+fn read_songs(songs: &Path) -> Vec<String> {
+    let content = std::fs::read_to_string(songs).unwrap_or_else(|err| panic!("Could not read song constants at {:?} with error {}", songs, err));
+    let mut songs: Vec<(u32, String)> = content.lines()
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some("#define"), Some(name), Some(id)) if name.starts_with("MUS_") => id.parse().ok().map(|id| (id, name.to_owned())),
+                _ => None,
+            }
+        })
+        .collect();
+    songs.sort();
+    songs.into_iter().map(|(_, name)| name).collect()
+}
+
+/// Gives each song the imported maps play an id, in the order of the project's song ids.
+/// Song ids in the project go past what a map file can store, so they are not kept.
+fn music_table(songs: &[String], maps: &[ImportedMap]) -> HashMap<String, u8> {
+    let mut music = HashMap::new();
+    let mut silent = false;
+    for map in maps.iter() {
+        if !songs.contains(&map.json.music) {
+            if !map.json.music.is_empty() {
+                eprintln!("Map {} plays {}, which is not a song in songs.h, so it is silent", map.json.name, map.json.music);
+            }
+            silent = true;
+        }
+    }
+    for song in songs.iter().filter(|song| maps.iter().any(|map| &map.json.music == *song)) {
+        let id = music.len() + 1;
+        if id > u8::MAX as usize {
+            panic!("Imported maps play more than {} songs, which is more than a map file can refer to", u8::MAX);
+        }
+        music.insert(song.clone(), id as u8);
+    }
+    if silent {
+        music.insert(String::from("MUS_NONE"), SILENCE);
+    }
+    music
+}
+
+/// Name of a song in the music table, such as `pallet` for `MUS_PALLET`.
+fn track_name(song: &str) -> String {
+    song.trim_start_matches("MUS_").to_lowercase()
+}
+
+/// Reads national dex numbers from the `NATIONAL_DEX_*` enum, keyed by their `SPECIES_*` constant.
+fn read_species(pokedex: &Path) -> HashMap<String, u16> {
+    let content = std::fs::read_to_string(pokedex).unwrap_or_else(|err| panic!("Could not read pokedex constants at {:?} with error {}", pokedex, err));
+    content.lines()
+        .map(str::trim)
+        .filter(|line| line.starts_with("NATIONAL_DEX_") && line.ends_with(','))
+        .enumerate()
+        .map(|(number, line)| (line.trim_end_matches(',').replacen("NATIONAL_DEX_", "SPECIES_", 1), number as u16))
+        .collect()
+}
+
+/// Places chunks next to the maps they connect to. Separate groups of connected maps are placed side by side.
+fn chunk_coordinates<'a>(maps: &'a [ImportedMap], by_id: &HashMap<&str, &'a ImportedMap>, layouts: &HashMap<String, LayoutJson>) -> HashMap<&'a str, (i32, i32)> {
+
+    let size = |map: &ImportedMap| layouts.get(&map.json.layout).map(|layout| (layout.width as i32, layout.height as i32)).unwrap_or_default();
+
+    let mut coords: HashMap<&str, (i32, i32)> = HashMap::new();
+    let mut next_x = 0;
+
+    for map in maps.iter().filter(|map| map.map_set.is_none()) {
+
+        if coords.contains_key(map.json.id.as_str()) {
+            continue;
+        }
+
+        coords.insert(&map.json.id, (next_x, 0));
+        let mut right = next_x;
+        let mut queue = VecDeque::new();
+        queue.push_back(map);
+
+        while let Some(map) = queue.pop_front() {
+            let (x, y) = coords[map.json.id.as_str()];
+            let (width, height) = size(map);
+            right = right.max(x + width);
+            for connection in map.json.connections.iter().flatten() {
+                let other = match by_id.get(connection.map.as_str()) {
+                    Some(other) => *other,
+                    None => continue,
+                };
+                if coords.contains_key(other.json.id.as_str()) {
+                    continue;
+                }
+                let (other_width, other_height) = size(other);
+                let position = match offset_direction(&connection.direction) {
+                    Some(Direction::Up) => (x + connection.offset, y - other_height),
+                    Some(Direction::Down) => (x + connection.offset, y + height),
+                    Some(Direction::Left) => (x - other_width, y + connection.offset),
+                    Some(Direction::Right) => (x + width, y + connection.offset),
+                    None => continue,
+                };
+                coords.insert(&other.json.id, position);
+                queue.push_back(other);
+            }
+        }
+
+        next_x = right;
+
+    }
+
+    coords
+
+}
+
+/// Direction of a connection, or `None` for dive and emerge connections, which do not place maps side by side.
+fn offset_direction(direction: &str) -> Option<Direction> {
+    match direction {
+        "up" => Some(Direction::Up),
+        "down" => Some(Direction::Down),
+        "left" => Some(Direction::Left),
+        "right" => Some(Direction::Right),
+        _ => None,
+    }
+}
+
+fn constant_id(name: &str, names: &[&str], kind: &str, map: &str) -> u8 {
+    match names.iter().position(|other| *other == name) {
+        Some(id) => id as u8,
+        None => {
+            if !name.is_empty() {
+                eprintln!("Map {} has unknown {} {}, using {}", map, kind, name, names[0]);
+            }
+            0
+        }
+    }
+}
+
+/// Turns a decomp name such as `PalletTown_PlayersHouse_1F` into `pallet_town_players_house_1f`.
+fn snake_case(name: &str) -> String {
+    let mut snake = String::with_capacity(name.len() + 8);
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            if c.is_ascii_uppercase() && previous.map(|previous| previous.is_ascii_lowercase()).unwrap_or_default() {
+                snake.push('_');
+            }
+            snake.push(c.to_ascii_lowercase());
+        } else if !snake.ends_with('_') && !snake.is_empty() {
+            snake.push('_');
+        }
+        previous = Some(c);
+    }
+    snake.trim_end_matches('_').to_owned()
+}
+
+/// Shortens a name that is too long for a map identifier, such as `pallet_town_players_house_1f`, to as much of it as fits
+/// followed by a hash of the whole name, so different names stay different. The original name is kept as the map's name.
+fn short_identifier(name: String) -> String {
+    if name.parse::<MapIdentifier>().is_ok() {
+        return name;
+    }
+    // FNV-1a, which unlike the hashers of the maps here gives the same hash on every run
+    let hash = name.bytes().fold(0x811c9dc5u32, |hash, byte| (hash ^ byte as u32).wrapping_mul(0x01000193));
+    let suffix = format!("_{:04x}", hash as u16);
+    let mut len = name.len();
+    loop {
+        let short = format!("{}{}", name[..len].trim_end_matches('_'), suffix);
+        if short.parse::<MapIdentifier>().is_ok() || len == 0 {
+            println!("    Shortened identifier {} to {}", name, short);
+            return short;
+        }
+        len -= 1;
+    }
+}
+
+/// Identifiers of imported maps and map sets are shortened to fit, so they always parse.
+fn identifier(name: &str) -> MapIdentifier {
+    name.parse().unwrap_or_else(|_| panic!("Could not use \"{}\" as a map identifier", name))
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> T {
+    let content = std::fs::read_to_string(path).unwrap_or_else(|err| panic!("Could not read {:?} with error {}", path, err));
+    serde_json::from_str(&content).unwrap_or_else(|err| panic!("Could not parse {:?} with error {}", path, err))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn short_identifier_fits_long_names() {
+        let long = snake_case("PalletTown_PlayersHouse_1F");
+        let short = short_identifier(long.clone());
+        assert!(short.parse::<MapIdentifier>().is_ok());
+        assert_eq!(short, short_identifier(long));
+        assert_ne!(short, short_identifier(snake_case("PalletTown_PlayersHouse_2F")));
+        assert_eq!(short_identifier(String::from("pallet_town")), "pallet_town");
+    }
+
+}
@@ -0,0 +1,3 @@
+//! Converters from other map formats into the directory layout `compile` reads.
+
+pub mod decomp;
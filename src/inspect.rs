@@ -0,0 +1,197 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::path::Path;
+
+use serde::Serialize;
+
+use firecore_world_lib::map::WorldMap;
+
+use crate::extension::MapExtensions;
+use crate::output::{CompiledWorld, WorldHeader, ReadError, read_world_file};
+
+/// Structured summary of a compiled world.
+pub struct WorldSummary {
+
+    pub header: WorldHeader,
+    pub chunks: usize,
+    pub map_sets: usize,
+    pub maps: Vec<MapSummary>,
+    pub palettes: Vec<PaletteSummary>,
+    /// Number of animated tiles and their total number of frames
+    pub animations: (usize, u32),
+    /// Number of tiles with a behavior
+    pub tile_behaviors: usize,
+    pub npc_types: Vec<NPCTypeSummary>,
+    /// Serialized size of each section of the world, in bytes
+    pub sections: Vec<(&'static str, usize)>,
+
+}
+
+pub struct MapSummary {
+
+    /// "chunk_id" for chunks and "map_set/map_id" for maps in map sets
+    pub location: String,
+    pub name: String,
+    pub width: usize,
+    pub height: usize,
+    pub warps: usize,
+    pub npcs: usize,
+    pub scripts: usize,
+    /// Number of wild encounter tables (grass, surfing, fishing, ...), including time and season variants
+    pub encounter_tables: usize,
+    pub bytes: usize,
+
+}
+
+pub struct PaletteSummary {
+
+    pub id: u8,
+    pub bytes: usize,
+    pub top_bytes: Option<usize>,
+
+}
+
+pub struct NPCTypeSummary {
+
+    pub identifier: String,
+    pub texture: usize,
+    pub battle_texture: Option<usize>,
+
+}
+
+pub fn inspect<P: AsRef<Path>>(world_file: P) -> Result<WorldSummary, ReadError> {
+    let (header, world) = read_world_file(world_file)?;
+    Ok(summarize(header, &world))
+}
+
+pub fn summarize(header: WorldHeader, compiled: &CompiledWorld) -> WorldSummary {
+
+    let world = &compiled.world;
+
+    let mut maps = Vec::new();
+
+    for (index, chunk) in world.manager.chunk_map.chunks.iter() {
+        maps.push(MapSummary::new(index.to_string(), &chunk.map, compiled.extensions.map(None, index)));
+    }
+
+    for (set_index, map_set) in world.manager.map_set_manager.map_sets.iter() {
+        for (index, map) in map_set.maps.iter() {
+            maps.push(MapSummary::new(format!("{}/{}", set_index, index), map, compiled.extensions.map(Some(set_index), index)));
+        }
+    }
+
+    maps.sort_by(|a, b| a.location.cmp(&b.location));
+
+    let mut palettes: Vec<PaletteSummary> = world.palettes.iter().map(|palette| PaletteSummary {
+        id: palette.id,
+        bytes: palette.bottom.len(),
+        top_bytes: compiled.extensions.top_palettes.get(&palette.id).map(Vec::len),
+    }).collect();
+
+    palettes.sort_by_key(|palette| palette.id);
+
+    let npc_types = world.npc_types.iter().map(|npc_type| NPCTypeSummary {
+        identifier: npc_type.config.identifier.to_string(),
+        texture: npc_type.texture.len(),
+        battle_texture: npc_type.battle_texture.as_ref().map(Vec::len),
+    }).collect();
+
+    let manager = serialized_size(&world.manager);
+    let chunks = serialized_size(&world.manager.chunk_map);
+    let map_sets = serialized_size(&world.manager.map_set_manager);
+
+    let sections = vec![
+        ("chunks", chunks),
+        ("map sets", map_sets),
+        ("other map data", manager.saturating_sub(chunks + map_sets)),
+        ("npc types", serialized_size(&world.npc_types)),
+        ("palettes", serialized_size(&world.palettes)),
+        ("top palettes", serialized_size(&compiled.extensions.top_palettes)),
+        ("animations", serialized_size(&compiled.extensions.animations)),
+        ("extensions", serialized_size(&compiled.extensions).saturating_sub(serialized_size(&compiled.extensions.top_palettes) + serialized_size(&compiled.extensions.animations))),
+    ];
+
+    WorldSummary {
+        header,
+        chunks: world.manager.chunk_map.chunks.len(),
+        map_sets: world.manager.map_set_manager.map_sets.len(),
+        maps,
+        palettes,
+        animations: (
+            compiled.extensions.animations.len(),
+            compiled.extensions.animations.iter().filter_map(|animation| crate::tileset::animation::frame_count(&animation.frames)).sum(),
+        ),
+        tile_behaviors: compiled.extensions.tile_behaviors.len(),
+        npc_types,
+        sections,
+    }
+
+}
+
+impl MapSummary {
+
+    fn new(location: String, map: &WorldMap, extensions: Option<&MapExtensions>) -> Self {
+        Self {
+            location,
+            name: map.name.clone(),
+            width: map.width as usize,
+            height: map.height as usize,
+            warps: map.warps.len(),
+            npcs: map.npc_manager.npcs.len(),
+            scripts: map.scripts.len(),
+            encounter_tables: map.wild.iter().count() + extensions.map(|extensions| extensions.encounters.len() + extensions.variants.len()).unwrap_or_default(),
+            bytes: serialized_size(map) + extensions.map(serialized_size).unwrap_or_default(),
+        }
+    }
+
+}
+
+pub(crate) fn serialized_size<T: Serialize + ?Sized>(value: &T) -> usize {
+    postcard::to_allocvec(value).map(|bytes| bytes.len()).unwrap_or_default()
+}
+
+impl Display for WorldSummary {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+
+        writeln!(f, "World file (format {}, builder {}, world lib {})", self.header.format_version, self.header.builder_version, self.header.world_lib_version)?;
+        writeln!(f, "    {} bytes, stored as {} bytes using {:?}", self.header.length, self.header.compressed_length, self.header.compression)?;
+        writeln!(f)?;
+
+        writeln!(f, "{} chunks, {} map sets, {} maps", self.chunks, self.map_sets, self.maps.len())?;
+        for map in self.maps.iter() {
+            writeln!(
+                f,
+                "    {:<32} {:<24} {:>3}x{:<3} warps: {:<3} npcs: {:<3} scripts: {:<3} encounter tables: {:<2} {:>8} bytes",
+                map.location, map.name, map.width, map.height, map.warps, map.npcs, map.scripts, map.encounter_tables, map.bytes
+            )?;
+        }
+        writeln!(f)?;
+
+        writeln!(f, "{} palettes", self.palettes.len())?;
+        for palette in self.palettes.iter() {
+            match palette.top_bytes {
+                Some(top_bytes) => writeln!(f, "    Palette #{:<3} {:>8} bytes, top layer: {:>8} bytes", palette.id, palette.bytes, top_bytes)?,
+                None => writeln!(f, "    Palette #{:<3} {:>8} bytes", palette.id, palette.bytes)?,
+            }
+        }
+        writeln!(f, "{} animated tiles with {} frames", self.animations.0, self.animations.1)?;
+        writeln!(f, "{} tiles with a behavior", self.tile_behaviors)?;
+        writeln!(f)?;
+
+        writeln!(f, "{} NPC types", self.npc_types.len())?;
+        for npc_type in self.npc_types.iter() {
+            match npc_type.battle_texture {
+                Some(battle_texture) => writeln!(f, "    {:<24} texture: {:>8} bytes, battle texture: {:>8} bytes", npc_type.identifier, npc_type.texture, battle_texture)?,
+                None => writeln!(f, "    {:<24} texture: {:>8} bytes", npc_type.identifier, npc_type.texture)?,
+            }
+        }
+        writeln!(f)?;
+
+        let total: usize = self.sections.iter().map(|(_, size)| size).sum();
+        writeln!(f, "Size breakdown")?;
+        for (section, size) in self.sections.iter() {
+            writeln!(f, "    {:<16} {:>10} bytes ({:.1}%)", section, size, *size as f64 * 100.0 / total.max(1) as f64)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,27 +1,102 @@
 extern crate firecore_world_lib;
 
 use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use firecore_world_lib::map::chunk::map::WorldChunkMap;
 use firecore_world_lib::map::manager::WorldMapManager;
 use firecore_world_lib::map::warp::WarpEntry;
+use firecore_world_lib::map::WorldMap;
 
 mod world;
 mod gba_map;
 
+pub mod dump;
+pub mod inspect;
+pub mod diff;
+pub mod decompile;
+pub mod report;
+pub mod tileset;
+pub mod music;
+pub mod import;
+
+use output::Compression;
+use dump::DumpFormat;
+use extension::{WorldExtensions, MapExtensions, EncounterKind};
+
+pub use gba_map::PaletteNaming;
+pub use world_format::{extension, output};
+
 // pub type ResultT<T> = Result<T, Box<dyn std::error::Error>>;
 
+#[derive(Debug, Clone, Default)]
+pub struct CompileOptions {
+
+    pub palette_naming: PaletteNaming,
+    pub compression: Compression,
+    /// Also write a human readable dump of the world next to the output file
+    pub dump: Option<DumpFormat>,
+    /// Only write the dump, skipping the binary world file. Requires `dump` to be set.
+    pub dump_only: bool,
+    /// Pack all palettes into a single tile atlas, storing identical tiles once
+    pub pack_atlas: bool,
+    /// Print which tiles are duplicated across palettes and which tiles no map uses
+    pub tile_report: bool,
+    /// Point every tile id at the first identical tile across all palettes
+    pub merge_duplicate_tiles: bool,
+    /// TOML file naming music tracks. Maps may then name their track, and every map's track must be in it.
+    pub music_table: Option<PathBuf>,
+    /// Globs of map directories to load, relative to the maps directory, such as `kanto/**`. Every map is loaded if empty.
+    pub include: Vec<String>,
+    /// Globs of map directories to skip, relative to the maps directory
+    pub exclude: Vec<String>,
+
+}
+
 pub fn compile<P: AsRef<Path>>(maps: P, tile_textures: P, npc_types: P, output_file: P) {
+    compile_with_options(maps, tile_textures, npc_types, output_file, CompileOptions::default())
+}
+
+pub fn compile_with_options<P: AsRef<Path>>(maps: P, tile_textures: P, npc_types: P, output_file: P, options: CompileOptions) {
+
+    if options.dump_only && options.dump.is_none() {
+        panic!("Compile options ask to only write a dump, but no dump format was given, so nothing would be written");
+    }
+
+    let music = options.music_table.as_ref().map(music::MusicTable::load);
 
     println!("Started loading maps and tile textures...");
-    let (manager, palettes) = world::map::load_maps(maps, tile_textures);
+    let (mut manager, mut palettes, mut extensions) = world::map::load_maps(maps, tile_textures, &options.palette_naming, music.as_ref(), &options.include, &options.exclude);
     println!("Finished loading maps and tile textures.");
 
     println!("Verifying maps and warps...");
     verify_warps(&manager);
     verify_connections(&manager.chunk_map);
 
+    println!("Verifying wild encounters...");
+    verify_wild(&manager, &extensions);
+
+    if let Some(music) = music.as_ref() {
+        println!("Verifying music...");
+        verify_music(&manager, music);
+    }
+
+    if options.tile_report || options.merge_duplicate_tiles {
+        println!("Analyzing tiles...");
+        let analysis = tileset::analysis::analyze_tiles(&palettes, &extensions, &manager);
+        if options.tile_report {
+            print!("{}", analysis);
+        }
+        if options.merge_duplicate_tiles {
+            tileset::remap_tiles(&mut manager, &mut extensions, &analysis.canonical);
+        }
+    }
+
+    if options.pack_atlas {
+        println!("Packing tile atlas...");
+        palettes = tileset::atlas::pack_palettes(palettes, &mut manager, &mut extensions);
+    }
+
     println!("Loading NPC types...");
     let npc_types = world::npc::npc_type::load_npc_types(npc_types);
 
@@ -32,19 +107,42 @@ pub fn compile<P: AsRef<Path>>(maps: P, tile_textures: P, npc_types: P, output_f
             std::fs::create_dir_all(parent).unwrap_or_else(|err| panic!("Could not create directories for output file with error {}", err));
         }
     }
-    
-    let mut file = std::fs::File::create(output_file).unwrap_or_else(|err| panic!("Could not create output file at {:?} with error {}", output_file, err));
 
-    let data = firecore_world_lib::serialized::SerializedWorld {
-        manager,
-        npc_types,
-        palettes,
+    let data = output::CompiledWorld {
+        world: firecore_world_lib::serialized::SerializedWorld {
+            manager,
+            npc_types,
+            palettes,
+        },
+        extensions,
     };
 
+    if let Some(format) = options.dump {
+        let dump_file = output_file.with_extension(format.extension());
+        println!("Dumping world to {:?}...", dump_file);
+        let dump = dump::dump_world(&data, format).unwrap_or_else(|err| panic!("Could not dump world as {:?} with error {}", format, err));
+        std::fs::write(&dump_file, dump).unwrap_or_else(|err| panic!("Could not write dump file at {:?} with error {}", dump_file, err));
+    }
+
+    if options.dump_only {
+        return;
+    }
+
+    let mut file = std::fs::File::create(output_file).unwrap_or_else(|err| panic!("Could not create output file at {:?} with error {}", output_file, err));
+
     println!("Saving data...");
-    let bytes = postcard::to_allocvec(&data).unwrap_or_else(|err| panic!("Could not serialize output file with error {}", err));
-    let bytes = file.write(&bytes).unwrap_or_else(|err| panic!("Could not write to output file with error {}", err));
-    println!("Wrote {} bytes to world file!", bytes);
+    let (header, bytes) = output::write_world(&data, options.compression).unwrap_or_else(|err| panic!("Could not serialize output file with error {}", err));
+    file.write_all(&bytes).unwrap_or_else(|err| panic!("Could not write to output file with error {}", err));
+    if header.compression != Compression::None {
+        println!(
+            "Compressed world from {} bytes to {} bytes ({:.1}%) using {:?}",
+            header.length,
+            header.compressed_length,
+            header.compressed_length as f64 * 100.0 / header.length.max(1) as f64,
+            header.compression,
+        );
+    }
+    println!("Wrote {} bytes to world file!", bytes.len());
 
 }
 
@@ -106,4 +204,61 @@ fn verify_connections(chunks: &WorldChunkMap) {
     if errors != 0 {
         panic!("Found {} errors in chunk connections.", errors)
     }
-}
\ No newline at end of file
+}
+
+fn verify_music(manager: &WorldMapManager, music: &music::MusicTable) {
+    let mut errors: u32 = 0;
+    let maps = manager.chunk_map.chunks.values().map(|chunk| &chunk.map).chain(manager.map_set_manager.map_sets.values().flat_map(|map_set| map_set.maps.values()));
+    for map in maps {
+        if music.name(map.music).is_none() {
+            eprintln!("Map {} plays music #{}, which is not in the music table", map.name, map.music);
+            errors += 1;
+        }
+    }
+    if errors != 0 {
+        panic!("Found {} errors in map music.", errors);
+    }
+}
+
+fn verify_wild(manager: &WorldMapManager, extensions: &WorldExtensions) {
+    let mut errors: u32 = 0;
+    for (index, chunk) in manager.chunk_map.chunks.iter() {
+        errors += verify_map_wild(&chunk.map, extensions.map(None, index));
+    }
+    for (set_index, map_set) in manager.map_set_manager.map_sets.iter() {
+        for (index, map) in map_set.maps.iter() {
+            errors += verify_map_wild(map, extensions.map(Some(set_index), index));
+        }
+    }
+    if errors != 0 {
+        panic!("Found {} errors in wild encounter tables.", errors);
+    }
+}
+
+fn verify_map_wild(map: &WorldMap, extensions: Option<&MapExtensions>) -> u32 {
+    let mut errors: u32 = 0;
+    let grass = map.wild.iter().map(|entry| ("grass", entry));
+    let others = extensions.into_iter().flat_map(|extensions| extensions.encounters.iter().map(|(kind, entry)| (kind.name(), entry)));
+    for (kind, entry) in grass.chain(others) {
+        for error in world::wild::verify_wild_entry(entry, &map.tiles) {
+            eprintln!("Map {}'s {} encounter table {}", map.name, kind, error);
+            errors += 1;
+        }
+    }
+    for variant in extensions.iter().flat_map(|extensions| extensions.variants.iter()) {
+        let name = world::wild::variant_path(std::path::Path::new(""), variant);
+        let has_base = match variant.kind {
+            EncounterKind::Grass => map.wild.is_some(),
+            kind => extensions.map(|extensions| extensions.encounters.contains_key(&kind)).unwrap_or_default(),
+        };
+        if !has_base {
+            eprintln!("Map {}'s encounter table {:?} has no base {} table to fall back to", map.name, name, variant.kind.name());
+            errors += 1;
+        }
+        for error in world::wild::verify_table(&variant.table) {
+            eprintln!("Map {}'s encounter table {:?} {}", map.name, name, error);
+            errors += 1;
+        }
+    }
+    errors
+}
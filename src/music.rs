@@ -0,0 +1,60 @@
+use std::path::Path;
+
+use ahash::AHashMap as HashMap;
+
+/// Names of the music tracks maps can play, loaded from a TOML file of `name = id` pairs.
+#[derive(Debug, Clone, Default)]
+pub struct MusicTable {
+
+    tracks: HashMap<String, u8>,
+    names: HashMap<u8, String>,
+
+}
+
+impl MusicTable {
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref();
+
+        let content = std::fs::read_to_string(path).unwrap_or_else(|err| panic!("Could not read music table at {:?} with error {}", path, err));
+        let tracks: HashMap<String, u8> = toml::from_str(&content).unwrap_or_else(|err| panic!("Could not parse music table at {:?} with error {}", path, err));
+
+        let mut sorted: Vec<(&String, &u8)> = tracks.iter().collect();
+        sorted.sort();
+
+        let mut names = HashMap::with_capacity(tracks.len());
+        let mut errors: u32 = 0;
+
+        for (name, id) in sorted {
+            if let Some(other) = names.insert(*id, name.clone()) {
+                eprintln!("Music tracks {} and {} both have id {}", other, name, id);
+                errors += 1;
+            }
+        }
+
+        if errors != 0 {
+            panic!("Found {} errors in music table at {:?}.", errors, path);
+        }
+
+        Self {
+            tracks,
+            names,
+        }
+    }
+
+    pub fn id(&self, name: &str) -> Option<u8> {
+        self.tracks.get(name).copied()
+    }
+
+    pub fn name(&self, id: u8) -> Option<&str> {
+        self.names.get(&id).map(String::as_str)
+    }
+
+    /// Every track, sorted by id.
+    pub fn tracks(&self) -> Vec<(u8, &str)> {
+        let mut tracks: Vec<(u8, &str)> = self.names.iter().map(|(id, name)| (*id, name.as_str())).collect();
+        tracks.sort_unstable();
+        tracks
+    }
+
+}
@@ -0,0 +1,168 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::Write;
+
+use firecore_world_lib::map::{
+    WorldMap,
+    MapIdentifier,
+    wild::table::WildPokemonTable,
+};
+
+use crate::extension::{EncounterKind, MapExtensions, TimePeriod, Season};
+use crate::output::CompiledWorld;
+use crate::world::wild::{ENCOUNTER_CHANCES, encounter_slots};
+
+/// Which species appear where, at what levels and how often.
+pub struct EncounterReport {
+
+    /// Sorted by species, then location
+    pub rows: Vec<EncounterRow>,
+    /// Species that were expected but do not appear in any table
+    pub missing: Vec<u16>,
+
+}
+
+pub struct EncounterRow {
+
+    pub species: u16,
+    /// "chunk_id" for chunks and "map_set/map_id" for maps in map sets
+    pub location: String,
+    pub map_name: String,
+    pub kind: EncounterKind,
+    pub time: Option<TimePeriod>,
+    pub season: Option<Season>,
+    pub min_level: u8,
+    pub max_level: u8,
+    /// Chance (out of 100) of an encounter on this table being this species
+    pub chance: u8,
+
+}
+
+/// Builds the report for every table in the world, listing any of the given species that never appear.
+pub fn encounter_report<I: IntoIterator<Item = u16>>(compiled: &CompiledWorld, species: I) -> EncounterReport {
+
+    let world = &compiled.world;
+
+    let mut rows = Vec::new();
+
+    for (index, chunk) in world.manager.chunk_map.chunks.iter() {
+        add_map(&mut rows, index.to_string(), &chunk.map, compiled.extensions.map(None, index));
+    }
+
+    for (set_index, map_set) in world.manager.map_set_manager.map_sets.iter() {
+        for (index, map) in map_set.maps.iter() {
+            add_map(&mut rows, location(set_index, index), map, compiled.extensions.map(Some(set_index), index));
+        }
+    }
+
+    rows.sort_by(|a, b| (a.species, &a.location, a.kind, a.time, a.season).cmp(&(b.species, &b.location, b.kind, b.time, b.season)));
+
+    let found: BTreeSet<u16> = rows.iter().map(|row| row.species).collect();
+    let mut missing: Vec<u16> = species.into_iter().filter(|species| !found.contains(species)).collect();
+    missing.sort_unstable();
+    missing.dedup();
+
+    EncounterReport {
+        rows,
+        missing,
+    }
+
+}
+
+fn location(set_index: &MapIdentifier, index: &MapIdentifier) -> String {
+    format!("{}/{}", set_index, index)
+}
+
+fn add_map(rows: &mut Vec<EncounterRow>, location: String, map: &WorldMap, extensions: Option<&MapExtensions>) {
+    if let Some(wild) = map.wild.as_ref() {
+        add_table(rows, &location, map, EncounterKind::Grass, None, None, &wild.table);
+    }
+    if let Some(extensions) = extensions {
+        for (kind, entry) in extensions.encounters.iter() {
+            add_table(rows, &location, map, *kind, None, None, &entry.table);
+        }
+        for variant in extensions.variants.iter() {
+            add_table(rows, &location, map, variant.kind, variant.time, variant.season, &variant.table);
+        }
+    }
+}
+
+/// Adds a row per species in the table, merging the slots a species appears in.
+fn add_table(rows: &mut Vec<EncounterRow>, location: &str, map: &WorldMap, kind: EncounterKind, time: Option<TimePeriod>, season: Option<Season>, table: &WildPokemonTable) {
+    let mut species: BTreeMap<u16, EncounterRow> = BTreeMap::new();
+    for (slot, chance) in encounter_slots(table).iter().zip(ENCOUNTER_CHANCES.iter()) {
+        let row = species.entry(slot.pokemon_id as u16).or_insert_with(|| EncounterRow {
+            species: slot.pokemon_id as u16,
+            location: location.to_owned(),
+            map_name: map.name.clone(),
+            kind,
+            time,
+            season,
+            min_level: slot.min_level,
+            max_level: slot.max_level,
+            chance: 0,
+        });
+        row.min_level = row.min_level.min(slot.min_level);
+        row.max_level = row.max_level.max(slot.max_level);
+        row.chance += chance;
+    }
+    rows.extend(species.into_iter().map(|(_, row)| row));
+}
+
+impl EncounterReport {
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("species,location,map,type,time,season,min_level,max_level,chance\n");
+        for row in self.rows.iter() {
+            writeln!(
+                csv,
+                "{},{},\"{}\",{},{},{},{},{},{}",
+                row.species,
+                row.location,
+                row.map_name.replace('"', "\"\""),
+                row.kind.name(),
+                row.time.map(|time| time.name()).unwrap_or_default(),
+                row.season.map(|season| season.name()).unwrap_or_default(),
+                row.min_level,
+                row.max_level,
+                row.chance,
+            ).unwrap();
+        }
+        csv
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# Wild encounters\n");
+
+        let mut species = None;
+        for row in self.rows.iter() {
+            if species != Some(row.species) {
+                species = Some(row.species);
+                writeln!(markdown, "\n## #{:03}\n", row.species).unwrap();
+                markdown.push_str("| Location | Map | Type | Time | Season | Levels | Chance |\n");
+                markdown.push_str("| --- | --- | --- | --- | --- | --- | --- |\n");
+            }
+            writeln!(
+                markdown,
+                "| {} | {} | {} | {} | {} | {}-{} | {}% |",
+                row.location,
+                row.map_name,
+                row.kind.name(),
+                row.time.map(|time| time.name()).unwrap_or("any"),
+                row.season.map(|season| season.name()).unwrap_or("any"),
+                row.min_level,
+                row.max_level,
+                row.chance,
+            ).unwrap();
+        }
+
+        if !self.missing.is_empty() {
+            markdown.push_str("\n## Species that appear nowhere\n\n");
+            for species in self.missing.iter() {
+                writeln!(markdown, "- #{:03}", species).unwrap();
+            }
+        }
+
+        markdown
+    }
+
+}
@@ -0,0 +1,4 @@
+//! Reports built from a compiled world for designers.
+
+pub mod encounters;
+pub mod music;
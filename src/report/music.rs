@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::music::MusicTable;
+use crate::output::CompiledWorld;
+
+/// Which maps play each music track.
+pub struct MusicReport {
+
+    /// Sorted by track id
+    pub tracks: Vec<TrackRow>,
+
+}
+
+pub struct TrackRow {
+
+    pub id: u8,
+    /// `None` when the track is not in the music table
+    pub name: Option<String>,
+    /// "chunk_id" for chunks and "map_set/map_id" for maps in map sets, with the map's name
+    pub maps: Vec<(String, String)>,
+
+}
+
+/// Builds the report for every map in the world, including tracks of the music table that no map plays.
+pub fn music_report(compiled: &CompiledWorld, music: &MusicTable) -> MusicReport {
+
+    let world = &compiled.world;
+
+    let mut tracks: BTreeMap<u8, Vec<(String, String)>> = music.tracks().into_iter().map(|(id, _)| (id, Vec::new())).collect();
+
+    for (index, chunk) in world.manager.chunk_map.chunks.iter() {
+        tracks.entry(chunk.map.music).or_default().push((index.to_string(), chunk.map.name.clone()));
+    }
+
+    for (set_index, map_set) in world.manager.map_set_manager.map_sets.iter() {
+        for (index, map) in map_set.maps.iter() {
+            tracks.entry(map.music).or_default().push((format!("{}/{}", set_index, index), map.name.clone()));
+        }
+    }
+
+    MusicReport {
+        tracks: tracks.into_iter().map(|(id, mut maps)| {
+            maps.sort();
+            TrackRow {
+                id,
+                name: music.name(id).map(str::to_owned),
+                maps,
+            }
+        }).collect(),
+    }
+
+}
+
+impl MusicReport {
+
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::from("# Music\n\n");
+        markdown.push_str("| Track | Name | Maps |\n");
+        markdown.push_str("| --- | --- | --- |\n");
+        for track in self.tracks.iter() {
+            writeln!(
+                markdown,
+                "| {} | {} | {} |",
+                track.id,
+                track.name.as_deref().unwrap_or("unknown"),
+                match track.maps.is_empty() {
+                    true => String::from("none"),
+                    false => track.maps.iter().map(|(location, name)| format!("{} ({})", name, location)).collect::<Vec<_>>().join(", "),
+                },
+            ).unwrap();
+        }
+        markdown
+    }
+
+}
@@ -0,0 +1,141 @@
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::Cursor;
+
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+
+use firecore_world_lib::{
+    TileId,
+    serialized::Palette,
+    map::manager::WorldMapManager,
+};
+
+use crate::extension::WorldExtensions;
+
+use super::{TILE_SIZE, TileKey};
+
+/// Duplicate and unused tiles across all palettes, by global tile id.
+pub struct TileAnalysis {
+
+    /// Groups of tiles with identical pixels, starting with the canonical (lowest) tile of each group
+    pub duplicates: Vec<Vec<TileId>>,
+    /// Tiles that no map or border uses
+    pub unused: Vec<TileId>,
+    /// The canonical tile of each tile, indexed by tile id
+    pub canonical: Vec<TileId>,
+    /// Palette id, first tile id and width (in tiles) of each palette, used to locate tiles for the report
+    palettes: Vec<(u8, TileId, u32)>,
+
+}
+
+pub fn analyze_tiles(palettes: &[Palette], extensions: &WorldExtensions, manager: &WorldMapManager) -> TileAnalysis {
+
+    let mut sorted: Vec<&Palette> = palettes.iter().collect();
+    sorted.sort_by_key(|palette| palette.id);
+
+    let mut start = 0;
+    let mut palette_starts = Vec::with_capacity(sorted.len());
+
+    for palette in sorted {
+        let (width, height) = image::io::Reader::new(Cursor::new(&palette.bottom))
+            .with_guessed_format()
+            .ok()
+            .and_then(|reader| reader.into_dimensions().ok())
+            .unwrap_or_else(|| panic!("Could not read dimensions of palette #{}", palette.id));
+        palette_starts.push((palette.id, start, width / TILE_SIZE));
+        start += ((width / TILE_SIZE) * (height / TILE_SIZE)) as TileId;
+    }
+
+    let tiles = super::split_palettes(palettes, extensions);
+
+    let mut groups: HashMap<TileKey, Vec<TileId>> = HashMap::new();
+    for (id, tile) in tiles.iter().enumerate() {
+        groups.entry(tile.key()).or_default().push(id as TileId);
+    }
+
+    let mut canonical: Vec<TileId> = (0..tiles.len() as TileId).collect();
+    let mut duplicates: Vec<Vec<TileId>> = groups.into_iter().map(|(_, group)| group).filter(|group| group.len() > 1).collect();
+    duplicates.sort();
+
+    for group in duplicates.iter() {
+        for tile in group.iter() {
+            canonical[*tile as usize] = group[0];
+        }
+    }
+
+    let mut used = HashSet::new();
+    for map in manager.chunk_map.chunks.values().map(|chunk| &chunk.map).chain(manager.map_set_manager.map_sets.values().flat_map(|map_set| map_set.maps.values())) {
+        used.extend(map.tiles.iter().copied());
+        used.extend(map.border.tiles.iter().copied());
+    }
+
+    let unused = (0..tiles.len() as TileId).filter(|tile| !used.contains(tile)).collect();
+
+    TileAnalysis {
+        duplicates,
+        unused,
+        canonical,
+        palettes: palette_starts,
+    }
+
+}
+
+impl TileAnalysis {
+
+    /// Palette id, tile index within the palette and pixel position of a tile.
+    pub fn locate(&self, tile: TileId) -> Option<(u8, TileId, (u32, u32))> {
+        self.palettes.iter().rev().find(|(_, start, _)| *start <= tile).map(|(id, start, columns)| {
+            let local = tile - start;
+            let columns = (*columns).max(1);
+            (*id, local, ((local as u32 % columns) * TILE_SIZE, (local as u32 / columns) * TILE_SIZE))
+        })
+    }
+
+    fn write_tile(&self, f: &mut Formatter<'_>, tile: TileId) -> FmtResult {
+        match self.locate(tile) {
+            Some((palette, local, (x, y))) => write!(f, "Palette #{} tile {} ({}, {})", palette, local, x, y),
+            None => write!(f, "tile #{}", tile),
+        }
+    }
+
+}
+
+impl Display for TileAnalysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+
+        let duplicate_count: usize = self.duplicates.iter().map(|group| group.len() - 1).sum();
+        writeln!(f, "Found {} duplicate tiles in {} groups", duplicate_count, self.duplicates.len())?;
+        for group in self.duplicates.iter() {
+            write!(f, "    ")?;
+            self.write_tile(f, group[0])?;
+            write!(f, " is duplicated by ")?;
+            for (index, tile) in group[1..].iter().enumerate() {
+                if index != 0 {
+                    write!(f, ", ")?;
+                }
+                self.write_tile(f, *tile)?;
+            }
+            writeln!(f)?;
+        }
+
+        writeln!(f, "Found {} tiles that no map uses", self.unused.len())?;
+        let mut palette = None;
+        for tile in self.unused.iter() {
+            if let Some((id, local, _)) = self.locate(*tile) {
+                if palette != Some(id) {
+                    if palette.is_some() {
+                        writeln!(f)?;
+                    }
+                    palette = Some(id);
+                    write!(f, "    Palette #{}: {}", id, local)?;
+                } else {
+                    write!(f, ", {}", local)?;
+                }
+            }
+        }
+        if palette.is_some() {
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
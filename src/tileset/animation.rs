@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use image::GenericImageView;
+use serde::{Serialize, Deserialize};
+
+use firecore_world_lib::TileId;
+
+use crate::extension::TileAnimation;
+
+use super::TILE_SIZE;
+
+/// An animated tile in a palette's animation file, `animations/<palette id>.ron` under the tile textures.
+#[derive(Serialize, Deserialize)]
+pub struct SerializedTileAnimation {
+
+    /// Tile id within the palette
+    pub tile: TileId,
+    /// Frame strip next to the animation file, with 16x16 frames laid out left to right
+    pub frames: String,
+    /// Seconds each frame is shown for
+    pub duration: f32,
+
+}
+
+/// Loads the animated tiles of each palette from `animations/<palette id>.ron`, converting their tile ids to global ids.
+pub fn load_animations<P: AsRef<Path>>(animations: P, palette_sizes: &HashMap<u8, u16>) -> Vec<TileAnimation> {
+    let animations_dir = animations.as_ref();
+
+    let mut animations = Vec::new();
+
+    if !animations_dir.is_dir() {
+        return animations;
+    }
+
+    let offsets = super::palette_offsets(palette_sizes);
+    let mut errors: u32 = 0;
+
+    // frame strips live next to the animation files
+    let paths: Vec<_> = crate::world::list_files(animations_dir, &[crate::world::RON_EXTENSION, "png"])
+        .into_iter()
+        .filter(|path| path.extension().map(|ext| ext == crate::world::RON_EXTENSION).unwrap_or_default())
+        .collect();
+
+    for file in paths {
+
+        let palette = match file.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<u8>().ok()) {
+            Some(palette) => palette,
+            None => {
+                eprintln!("Tile animation file {:?} is not named after a palette id", file);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let (offset, size) = match (offsets.get(&palette), palette_sizes.get(&palette)) {
+            (Some(offset), Some(size)) => (*offset, *size),
+            _ => {
+                eprintln!("Tile animation file {:?} is for palette #{}, which does not exist", file, palette);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let content = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not read tile animation file at {:?} with error {}", file, err));
+        let serialized: Vec<SerializedTileAnimation> = ron::from_str(&content).unwrap_or_else(|err| panic!("Could not parse tile animation file at {:?} with error {}", file, err));
+
+        let mut tiles = HashSet::new();
+
+        for animation in serialized {
+
+            if animation.tile >= size {
+                eprintln!("Tile animation for tile {} in {:?} is outside of palette #{}, which has {} tiles", animation.tile, file, palette, size);
+                errors += 1;
+                continue;
+            }
+
+            if !tiles.insert(animation.tile) {
+                eprintln!("Tile {} is animated more than once in {:?}", animation.tile, file);
+                errors += 1;
+                continue;
+            }
+
+            if animation.duration.is_nan() || animation.duration <= 0.0 {
+                eprintln!("Tile animation for tile {} in {:?} has a frame duration of {}, but it must be above 0", animation.tile, file, animation.duration);
+                errors += 1;
+            }
+
+            let strip = animations_dir.join(&animation.frames);
+            let frames = match std::fs::read(&strip) {
+                Ok(frames) => frames,
+                Err(err) => {
+                    eprintln!("Could not read frame strip at {:?} with error {}", strip, err);
+                    errors += 1;
+                    continue;
+                }
+            };
+
+            let (width, height) = match image::load_from_memory_with_format(&frames, image::ImageFormat::Png) {
+                Ok(image) => image.dimensions(),
+                Err(err) => {
+                    eprintln!("Could not decode frame strip at {:?} with error {}", strip, err);
+                    errors += 1;
+                    continue;
+                }
+            };
+
+            if height != TILE_SIZE || width == 0 || width % TILE_SIZE != 0 {
+                eprintln!("Frame strip at {:?} is {}x{} pixels, but it must be a row of {}x{} frames", strip, width, height, TILE_SIZE, TILE_SIZE);
+                errors += 1;
+                continue;
+            }
+
+            if width / TILE_SIZE < 2 {
+                eprintln!("Frame strip at {:?} has a single frame, but an animation needs at least 2", strip);
+                errors += 1;
+                continue;
+            }
+
+            animations.push(TileAnimation {
+                tile: offset + animation.tile,
+                frames,
+                duration: animation.duration,
+            });
+
+        }
+
+    }
+
+    if errors != 0 {
+        panic!("Found {} errors in tile animations at {:?}.", errors, animations_dir);
+    }
+
+    animations.sort_by_key(|animation| animation.tile);
+
+    animations
+
+}
+
+/// Number of frames in a frame strip.
+pub fn frame_count(frames: &[u8]) -> Option<u32> {
+    image::io::Reader::new(std::io::Cursor::new(frames))
+        .with_guessed_format()
+        .ok()
+        .and_then(|reader| reader.into_dimensions().ok())
+        .map(|(width, _)| width / TILE_SIZE)
+}
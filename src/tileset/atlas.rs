@@ -0,0 +1,103 @@
+use ahash::AHashMap as HashMap;
+use image::{GenericImageView, RgbaImage};
+
+use firecore_world_lib::{
+    TileId,
+    serialized::Palette,
+    map::manager::WorldMapManager,
+};
+
+use crate::extension::WorldExtensions;
+
+use super::{TILE_SIZE, Tile, TileKey};
+
+/// Tiles packed into a single image, with identical tiles stored once.
+pub struct TileAtlas {
+
+    pub image: RgbaImage,
+    /// Top layer of the atlas, if any packed tile has one
+    pub top: Option<RgbaImage>,
+    /// Maps each source tile id to its id in the atlas
+    pub remap: Vec<TileId>,
+
+}
+
+pub fn pack_atlas(tiles: &[Tile], columns: u32) -> TileAtlas {
+
+    let mut unique: Vec<&Tile> = Vec::new();
+    let mut ids: HashMap<TileKey, TileId> = HashMap::new();
+    let mut remap = Vec::with_capacity(tiles.len());
+
+    for tile in tiles.iter() {
+        let id = *ids.entry(tile.key()).or_insert_with(|| {
+            unique.push(tile);
+            (unique.len() - 1) as TileId
+        });
+        remap.push(id);
+    }
+
+    let columns = columns.max(1);
+    let rows = (unique.len() as u32 + columns - 1) / columns;
+
+    let mut image = RgbaImage::new(columns * TILE_SIZE, rows.max(1) * TILE_SIZE);
+    let mut top = match unique.iter().any(|tile| tile.top.is_some()) {
+        true => Some(RgbaImage::new(image.width(), image.height())),
+        false => None,
+    };
+
+    for (index, tile) in unique.into_iter().enumerate() {
+        let index = index as u32;
+        let (x, y) = ((index % columns) * TILE_SIZE, (index / columns) * TILE_SIZE);
+        image::imageops::replace(&mut image, &tile.bottom, x, y);
+        if let (Some(top), Some(tile)) = (top.as_mut(), tile.top.as_ref()) {
+            image::imageops::replace(top, tile, x, y);
+        }
+    }
+
+    TileAtlas {
+        image,
+        top,
+        remap,
+    }
+
+}
+
+/// Packs all palettes (and their top layers) into one atlas palette and remaps the world's tile ids to it.
+pub fn pack_palettes(palettes: Vec<Palette>, manager: &mut WorldMapManager, extensions: &mut WorldExtensions) -> Vec<Palette> {
+
+    let tiles = super::split_palettes(&palettes, extensions);
+
+    // keep the width of the first palette so the atlas looks like the tilesets it came from
+    let columns = palettes.iter().min_by_key(|palette| palette.id)
+        .and_then(|palette| image::load_from_memory_with_format(&palette.bottom, image::ImageFormat::Png).ok())
+        .map(|image| image.width() / TILE_SIZE)
+        .unwrap_or(8);
+
+    let atlas = pack_atlas(&tiles, columns);
+
+    super::remap_tiles(manager, extensions, &atlas.remap);
+
+    let bottom = encode(atlas.image);
+
+    extensions.top_palettes.clear();
+    if let Some(top) = atlas.top {
+        extensions.top_palettes.insert(0, encode(top));
+    }
+
+    println!("Packed {} tiles from {} palettes into an atlas of {} tiles", tiles.len(), palettes.len(), atlas.remap.iter().max().map(|max| *max as usize + 1).unwrap_or_default());
+
+    vec![
+        Palette {
+            id: 0,
+            bottom,
+        }
+    ]
+
+}
+
+fn encode(image: RgbaImage) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    image::DynamicImage::ImageRgba8(image).write_to(&mut bytes, image::ImageOutputFormat::Png)
+        .unwrap_or_else(|err| panic!("Could not encode tile atlas with error {}", err));
+    bytes
+}
@@ -0,0 +1,106 @@
+use std::path::Path;
+
+use ahash::AHashMap as HashMap;
+use serde::{Serialize, Deserialize};
+
+use firecore_world_lib::TileId;
+
+use crate::extension::TileBehavior;
+
+/// A range of tiles in a palette's attribute file, `attributes/<palette id>.ron` under the tile textures.
+#[derive(Serialize, Deserialize)]
+pub struct SerializedTileAttributes {
+
+    /// First tile id of the range within the palette
+    pub start: TileId,
+    /// Last tile id of the range within the palette (inclusive)
+    pub end: TileId,
+    pub behavior: TileBehavior,
+
+}
+
+/// Loads the tile behaviors of each palette from `attributes/<palette id>.ron`, keyed by global tile id.
+pub fn load_attributes<P: AsRef<Path>>(attributes: P, palette_sizes: &HashMap<u8, u16>) -> HashMap<TileId, TileBehavior> {
+    let attributes_dir = attributes.as_ref();
+
+    let mut behaviors = HashMap::new();
+
+    if !attributes_dir.is_dir() {
+        return behaviors;
+    }
+
+    let offsets = super::palette_offsets(palette_sizes);
+    let mut errors: u32 = 0;
+
+    let paths = crate::world::list_files(attributes_dir, &[crate::world::RON_EXTENSION]);
+
+    for file in paths {
+
+        let palette = match file.file_stem().and_then(|stem| stem.to_str()).and_then(|stem| stem.parse::<u8>().ok()) {
+            Some(palette) => palette,
+            None => {
+                eprintln!("Tile attribute file {:?} is not named after a palette id", file);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let (offset, size) = match (offsets.get(&palette), palette_sizes.get(&palette)) {
+            (Some(offset), Some(size)) => (*offset, *size),
+            _ => {
+                eprintln!("Tile attribute file {:?} is for palette #{}, which does not exist", file, palette);
+                errors += 1;
+                continue;
+            }
+        };
+
+        let content = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not read tile attribute file at {:?} with error {}", file, err));
+        let ranges: Vec<SerializedTileAttributes> = ron::from_str(&content).unwrap_or_else(|err| panic!("Could not parse tile attribute file at {:?} with error {}", file, err));
+
+        for range in ranges {
+
+            if range.start > range.end || range.end >= size {
+                eprintln!("Tile range {}-{} ({:?}) in {:?} is not within palette #{}, which has {} tiles", range.start, range.end, range.behavior, file, palette, size);
+                errors += 1;
+                continue;
+            }
+
+            for tile in range.start..=range.end {
+                if let Some(other) = behaviors.insert(offset + tile, range.behavior) {
+                    if other != range.behavior {
+                        eprintln!("Tile {} in {:?} is both {:?} and {:?}", tile, file, other, range.behavior);
+                        errors += 1;
+                    }
+                }
+            }
+
+        }
+
+    }
+
+    if errors != 0 {
+        panic!("Found {} errors in tile attributes at {:?}.", errors, attributes_dir);
+    }
+
+    behaviors
+
+}
+
+/// Groups tile behaviors back into ranges of consecutive tiles, for tiles from `start` up to (not including) `end`.
+pub fn attribute_ranges(behaviors: &HashMap<TileId, TileBehavior>, start: TileId, end: TileId) -> Vec<SerializedTileAttributes> {
+    let mut ranges: Vec<SerializedTileAttributes> = Vec::new();
+    for tile in start..end {
+        if let Some(behavior) = behaviors.get(&tile) {
+            let local = tile - start;
+            match ranges.last_mut() {
+                Some(range) if range.behavior == *behavior && range.end + 1 == local => range.end = local,
+                _ => ranges.push(SerializedTileAttributes {
+                    start: local,
+                    end: local,
+                    behavior: *behavior,
+                }),
+            }
+        }
+    }
+    ranges
+}
@@ -0,0 +1,150 @@
+use ahash::AHashMap as HashMap;
+use image::RgbaImage;
+
+use firecore_world_lib::{
+    TileId,
+    serialized::Palette,
+    map::{
+        WorldMap,
+        manager::WorldMapManager,
+    },
+};
+
+use crate::extension::{WorldExtensions, MapExtensions, TileBehavior};
+
+pub mod atlas;
+pub mod analysis;
+pub mod animation;
+pub mod attribute;
+
+/// Width and height of a tile in pixels.
+pub const TILE_SIZE: u32 = 16;
+
+pub type TileKey<'a> = (&'a [u8], Option<&'a [u8]>, Option<TileId>, Option<TileBehavior>);
+
+/// A tile of a palette, with the part of its top layer that covers it.
+pub struct Tile {
+
+    pub bottom: RgbaImage,
+    /// `None` when the palette has no top layer or the top layer is fully transparent over this tile
+    pub top: Option<RgbaImage>,
+    /// Global tile id of the tile if it is animated
+    pub animation: Option<TileId>,
+    pub behavior: Option<TileBehavior>,
+
+}
+
+impl Tile {
+
+    /// Pixels of both layers, so tiles that only differ in their top layer are told apart.
+    /// Animated tiles are never identical to another tile, and tiles with different behaviors are kept apart.
+    pub fn key(&self) -> TileKey {
+        (self.bottom.as_raw().as_slice(), self.top.as_ref().map(|top| top.as_raw().as_slice()), self.animation, self.behavior)
+    }
+
+}
+
+/// Splits the palettes and their top layers into tiles, indexed by global tile id.
+pub fn split_palettes(palettes: &[Palette], extensions: &WorldExtensions) -> Vec<Tile> {
+    let mut palettes: Vec<&Palette> = palettes.iter().collect();
+    palettes.sort_by_key(|palette| palette.id);
+    let mut tiles: Vec<Tile> = palettes.into_iter().flat_map(|palette| split_palette(palette, extensions.top_palettes.get(&palette.id))).collect();
+    for animation in extensions.animations.iter() {
+        if let Some(tile) = tiles.get_mut(animation.tile as usize) {
+            tile.animation = Some(animation.tile);
+        }
+    }
+    for (id, behavior) in extensions.tile_behaviors.iter() {
+        if let Some(tile) = tiles.get_mut(*id as usize) {
+            tile.behavior = Some(*behavior);
+        }
+    }
+    tiles
+}
+
+/// Splits a palette and its top layer into tiles, row by row.
+pub fn split_palette(palette: &Palette, top: Option<&Vec<u8>>) -> Vec<Tile> {
+    let bottom = split_tiles(&decode_palette(palette.id, &palette.bottom));
+    let mut top = top.map(|top| split_tiles(&decode_palette(palette.id, top))).unwrap_or_default().into_iter();
+    bottom.into_iter().map(|bottom| Tile {
+        bottom,
+        top: top.next().filter(|top| top.pixels().any(|pixel| pixel[3] != 0)),
+        animation: None,
+        behavior: None,
+    }).collect()
+}
+
+pub fn decode_palette(id: u8, bytes: &[u8]) -> RgbaImage {
+    image::load_from_memory_with_format(bytes, image::ImageFormat::Png)
+        .unwrap_or_else(|err| panic!("Could not decode palette #{} with error {}", id, err))
+        .to_rgba8()
+}
+
+/// First global tile id of each palette. Global ids number the tiles of all palettes in palette id order.
+pub fn palette_offsets(palette_sizes: &HashMap<u8, u16>) -> HashMap<u8, TileId> {
+    let mut ids: Vec<&u8> = palette_sizes.keys().collect();
+    ids.sort();
+    let mut offset = 0;
+    ids.into_iter().map(|id| {
+        let start = offset;
+        offset += palette_sizes[id];
+        (*id, start)
+    }).collect()
+}
+
+/// Splits an image into tiles, row by row.
+pub fn split_tiles(image: &RgbaImage) -> Vec<RgbaImage> {
+    let columns = image.width() / TILE_SIZE;
+    let rows = image.height() / TILE_SIZE;
+    let mut tiles = Vec::with_capacity((columns * rows) as usize);
+    for y in 0..rows {
+        for x in 0..columns {
+            tiles.push(image::imageops::crop_imm(image, x * TILE_SIZE, y * TILE_SIZE, TILE_SIZE, TILE_SIZE).to_image());
+        }
+    }
+    tiles
+}
+
+/// Replaces every tile id in the world's maps (tiles, borders and wild encounter tiles) and tile animations with `remap[id]`.
+pub fn remap_tiles(manager: &mut WorldMapManager, extensions: &mut WorldExtensions, remap: &[TileId]) {
+    for animation in extensions.animations.iter_mut() {
+        remap_tile(&mut animation.tile, remap);
+    }
+    extensions.animations.sort_by_key(|animation| animation.tile);
+    // tiles only share an id after remapping if they have the same behavior
+    extensions.tile_behaviors = extensions.tile_behaviors.drain().map(|(mut tile, behavior)| {
+        remap_tile(&mut tile, remap);
+        (tile, behavior)
+    }).collect();
+    for (index, chunk) in manager.chunk_map.chunks.iter_mut() {
+        remap_map(&mut chunk.map, extensions.chunks.get_mut(index), remap);
+    }
+    for (set_index, map_set) in manager.map_set_manager.map_sets.iter_mut() {
+        let mut set_extensions = extensions.map_sets.get_mut(set_index);
+        for (index, map) in map_set.maps.iter_mut() {
+            remap_map(map, set_extensions.as_mut().and_then(|maps| maps.get_mut(index)), remap);
+        }
+    }
+}
+
+fn remap_map(map: &mut WorldMap, extensions: Option<&mut MapExtensions>, remap: &[TileId]) {
+    let tiles = map.tiles.iter_mut()
+        .chain(map.border.tiles.iter_mut())
+        .chain(map.wild.iter_mut().flat_map(|wild| wild.tiles.iter_mut().flatten()));
+    for tile in tiles {
+        remap_tile(tile, remap);
+    }
+    if let Some(extensions) = extensions {
+        for entry in extensions.encounters.values_mut() {
+            for tile in entry.tiles.iter_mut().flatten() {
+                remap_tile(tile, remap);
+            }
+        }
+    }
+}
+
+fn remap_tile(tile: &mut TileId, remap: &[TileId]) {
+    if let Some(remapped) = remap.get(*tile as usize) {
+        *tile = *remapped;
+    }
+}
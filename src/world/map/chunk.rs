@@ -1,15 +1,17 @@
 use std::path::PathBuf;
 
-use ahash::AHashMap as HashMap;
 use firecore_world_lib::map::MapIdentifier;
 use crate::world::SerializedChunkMap;
+use crate::extension::MapExtensions;
+use crate::gba_map::PaletteLayout;
+use crate::music::MusicTable;
 
 use firecore_world_lib::map::chunk::WorldChunk;
 
-pub fn new_chunk_map(root_path: &PathBuf, palette_sizes: &HashMap<u8, u16>, serialized_chunk: SerializedChunkMap) -> (MapIdentifier, WorldChunk) {
+pub fn new_chunk_map(root_path: &PathBuf, palettes: &PaletteLayout, music: Option<&MusicTable>, serialized_chunk: SerializedChunkMap) -> (MapIdentifier, WorldChunk, MapExtensions) {
     println!("    Loading chunk map {}", serialized_chunk.config.name);
 
-    let (identifier, map) = super::load_map_from_config(root_path, palette_sizes, serialized_chunk.config);
+    let (identifier, map, extensions) = super::load_map_from_config(root_path, palettes, music, serialized_chunk.config);
     (
         identifier,
         WorldChunk {
@@ -17,7 +19,8 @@ pub fn new_chunk_map(root_path: &PathBuf, palette_sizes: &HashMap<u8, u16>, seri
             map,
             coords: serialized_chunk.coords,
             connections: serialized_chunk.connections,
-        }
+        },
+        extensions,
     )
     
 }
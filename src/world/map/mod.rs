@@ -1,6 +1,7 @@
 use std::path::{Path, PathBuf};
 
 use ahash::AHashMap as HashMap;
+use glob::Pattern;
 
 use firecore_world_lib::{
     serialized::Palette,
@@ -20,45 +21,146 @@ use firecore_world_lib::{
     }
 };
 
-use crate::gba_map::{get_gba_map, fix_tiles, fill_palette_map};
+use crate::gba_map::{get_gba_map, fix_tiles, default_tilesets, tile_lookup, fill_palette_map, PaletteNaming, PaletteLayout};
+use crate::extension::{WorldExtensions, MapExtensions, MapHeader, Weather, MapType};
+use crate::music::MusicTable;
 
 use super::MapConfig;
 
 pub mod chunk;
 pub mod set;
 
-pub fn load_maps<P: AsRef<Path>>(maps: P, tile_textures: P) -> (WorldMapManager, Vec<Palette>) {
+/// Which map directories are loaded, by globs matched against their path relative to the maps directory.
+/// Every directory is included if there are no include globs.
+struct MapFilter {
+
+    include: Vec<Pattern>,
+    exclude: Vec<Pattern>,
+
+}
+
+impl MapFilter {
+
+    fn new(include: &[String], exclude: &[String]) -> Self {
+        let patterns = |globs: &[String]| globs.iter().map(|glob| Pattern::new(glob).unwrap_or_else(|err| panic!("Could not parse map glob \"{}\" with error {}", glob, err))).collect();
+        Self {
+            include: patterns(include),
+            exclude: patterns(exclude),
+        }
+    }
+
+    fn included(&self, path: &Path) -> bool {
+        self.include.is_empty() || self.include.iter().any(|pattern| pattern.matches_path(path))
+    }
+
+    fn excluded(&self, path: &Path) -> bool {
+        self.exclude.iter().any(|pattern| pattern.matches_path(path))
+    }
+
+}
+
+/// Directories that hold the parts of a map or template rather than maps.
+const MAP_PART_DIRS: &[&str] = &["warps", "npcs", "scripts", "wild", "animations", "attributes"];
+
+/// Whether a directory holds the parts of a map or template, so it is never searched for maps.
+pub(crate) fn is_map_part(dir: &Path) -> bool {
+    dir.file_name().and_then(|name| name.to_str()).map(|name| MAP_PART_DIRS.contains(&name)).unwrap_or_default()
+}
+
+/// Searches `dir` for map configurations. A directory with `.ron` files holds a chunk or map set and is not searched further,
+/// while other directories (such as one per region) are searched recursively. Map templates and the parts of maps are skipped.
+fn discover(maps: &Path, dir: &Path, filter: &MapFilter, found: &mut Vec<(PathBuf, Vec<PathBuf>)>) {
+
+    let relative = dir.strip_prefix(maps).unwrap_or(dir);
+
+    if filter.excluded(relative) || super::template::is_template(dir) || is_map_part(dir) {
+        return;
+    }
+
+    let files = ron_files(dir);
+
+    if files.is_empty() {
+        for child in list_dirs(dir) {
+            discover(maps, &child, filter, found);
+        }
+    } else if filter.included(relative) {
+        found.push((dir.to_path_buf(), files));
+    }
+
+}
+
+/// Subdirectories of a directory, sorted.
+pub(crate) fn list_dirs(dir: &Path) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = std::fs::read_dir(dir)
+        .unwrap_or_else(|err| panic!("Could not read directory at {:?} with error {}", dir, err))
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.is_dir())
+        .collect();
+    dirs.sort();
+    dirs
+}
+
+/// Map configurations in a directory, sorted, warning about files that are neither configurations nor map files.
+pub(crate) fn ron_files(dir: &Path) -> Vec<PathBuf> {
+    super::list_files(dir, &[super::RON_EXTENSION, super::MAP_EXTENSION])
+        .into_iter()
+        .filter(|path| path.extension().map(|extension| extension == super::RON_EXTENSION).unwrap_or_default())
+        .collect()
+}
+
+pub fn load_maps<P: AsRef<Path>>(maps: P, tile_textures: P, palette_naming: &PaletteNaming, music: Option<&MusicTable>, include: &[String], exclude: &[String]) -> (WorldMapManager, Vec<Palette>, WorldExtensions) {
 
     let maps = maps.as_ref();
     let tile_textures = tile_textures.as_ref();
 
     let mut chunk_map = WorldChunkMap::default();
     let mut map_set_manager = WorldMapSetManager::default();
-    let (palette_sizes, palettes) = fill_palette_map(tile_textures);
-    println!("Loaded {} palettes", palette_sizes.len());
+    let mut extensions = WorldExtensions::default();
+    let (palette_sizes, palettes, top_palettes) = fill_palette_map(tile_textures, palette_naming);
+    println!("Loaded {} palettes ({} with a top layer)", palette_sizes.len(), top_palettes.len());
+    extensions.top_palettes = top_palettes;
+    extensions.animations = crate::tileset::animation::load_animations(tile_textures.join("animations"), &palette_sizes);
+    println!("Loaded {} animated tiles", extensions.animations.len());
+    extensions.tile_behaviors = crate::tileset::attribute::load_attributes(tile_textures.join("attributes"), &palette_sizes);
+    println!("Loaded behaviors of {} tiles", extensions.tile_behaviors.len());
+    let palette_layout = PaletteLayout::new(palette_sizes);
 
     println!("Loading maps...");
 
-    for worlds in std::fs::read_dir(maps).unwrap_or_else(|err| panic!("Could not read directory at {:?} with error {}", maps, err)) {
-        let worlds = worlds.unwrap_or_else(|err| panic!("Could not get directory entry under {:?} with error {}", maps, err)).path();
-        if let Ok(dir) = std::fs::read_dir(&worlds) {
-            for entry in dir {
-                if let Ok(entry) = entry {
-                    let file = entry.path();
-                    if let Some(ext) = file.extension() {
-                        if ext == std::ffi::OsString::from("ron") {
-                            let (cm, ms) = load_map(&palette_sizes, &worlds, &file);
-                            if let Some((index, chunk)) = cm {
-                                chunk_map.chunks.insert(index, chunk);
-                            } else if let Some((index, map_set)) = ms {
-                                map_set_manager.map_sets.insert(index, map_set);
-                            }
-                        }
-                    }
+    let filter = MapFilter::new(include, exclude);
+    let mut map_dirs = Vec::new();
+    for dir in list_dirs(maps) {
+        discover(maps, &dir, &filter, &mut map_dirs);
+    }
+
+    let mut errors: u32 = 0;
+
+    for (dir, files) in map_dirs {
+        for file in files {
+            let (cm, ms) = load_map(&palette_layout, music, &dir, &file, &mut errors);
+            if let Some((index, chunk, chunk_extensions)) = cm {
+                if chunk_map.chunks.contains_key(&index) {
+                    eprintln!("Chunk {} at {:?} has the same index as another chunk", index, file);
+                    errors += 1;
+                    continue;
+                }
+                extensions.chunks.insert(index.clone(), chunk_extensions);
+                chunk_map.chunks.insert(index, chunk);
+            } else if let Some((index, map_set, map_set_extensions)) = ms {
+                if map_set_manager.map_sets.contains_key(&index) {
+                    eprintln!("Map set {} at {:?} has the same identifier as another map set", index, file);
+                    errors += 1;
+                    continue;
                 }
+                extensions.map_sets.insert(index.clone(), map_set_extensions);
+                map_set_manager.map_sets.insert(index, map_set);
             }
         }
-        
+    }
+
+    if errors != 0 {
+        panic!("Found {} errors in map configurations under {:?}.", errors, maps);
     }
 
     let palettes = palettes.into_iter().map(
@@ -79,18 +181,21 @@ pub fn load_maps<P: AsRef<Path>>(maps: P, tile_textures: P) -> (WorldMapManager,
 
     (
         manager,
-        palettes
+        palettes,
+        extensions,
     )
 
 }
 
 fn load_map(
-    palette_sizes: &HashMap<u8, u16>, 
+    palettes: &PaletteLayout,
+    music: Option<&MusicTable>,
     root_path: &PathBuf, 
-    file: &PathBuf
+    file: &PathBuf,
+    errors: &mut u32,
 ) -> (
-    Option<(MapIdentifier, WorldChunk)>, 
-    Option<(MapIdentifier, WorldMapSet)>
+    Option<(MapIdentifier, WorldChunk, MapExtensions)>,
+    Option<(MapIdentifier, WorldMapSet, HashMap<MapIdentifier, MapExtensions>)>
 ) 
     {
     
@@ -102,7 +207,7 @@ fn load_map(
         Ok(serialized_chunk) => {
             (
                 Some(
-                    chunk::new_chunk_map(root_path, palette_sizes, serialized_chunk)
+                    chunk::new_chunk_map(root_path, palettes, music, serialized_chunk)
                 ), 
                 None
             )
@@ -113,7 +218,7 @@ fn load_map(
                     (
                         None, 
                         Some(
-                            set::load_map_set(root_path, palette_sizes, serialized_map_set)
+                            set::load_map_set(root_path, palettes, music, serialized_map_set, errors)
                         )
                     )
                 }
@@ -129,31 +234,92 @@ fn load_map(
     }
 }
 
-pub fn load_map_from_config<P: AsRef<Path>>(root_path: P, palette_sizes: &HashMap<u8, u16>, config: MapConfig) -> (MapIdentifier, WorldMap) {
+pub fn load_map_from_config<P: AsRef<Path>>(root_path: P, palettes: &PaletteLayout, music: Option<&MusicTable>, mut config: MapConfig) -> (MapIdentifier, WorldMap, MapExtensions) {
     let root_path = root_path.as_ref();
     // println!("Loading map: \"{}\"", map_config.name);
+    let sources = super::template::apply_template(root_path, &mut config);
+    let template = sources.template.as_deref();
+    let file = sources.file_dir.join(&config.file);
     let mut gba_map = get_gba_map(
-        std::fs::read(root_path.join(config.file)).unwrap_or_else(|err| panic!("Could not get map file at {:?} with error {}", root_path, err))
-    );
-    fix_tiles(&mut gba_map, palette_sizes);
+        std::fs::read(&file).unwrap_or_else(|err| panic!("Could not get map file at {:?} with error {}", file, err)),
+        config.border.map(|border| (border.width, border.height)),
+    ).unwrap_or_else(|err| panic!("Could not parse map file at {:?} with error {}", file, err));
+    let tilesets = config.tilesets.clone().unwrap_or_else(|| default_tilesets(&gba_map, palettes));
+    let lookup = tile_lookup(&tilesets, palettes).unwrap_or_else(|err| panic!("Could not use the tilesets of map {} with error {}", config.name, err));
+    fix_tiles(&mut gba_map, &lookup).unwrap_or_else(|err| panic!("Could not convert tiles of map {} with error {}", config.name, err));
+
+    let music = match config.music.as_ref() {
+        Some(name) => music
+            .unwrap_or_else(|| panic!("Map {} names its music track \"{}\", but no music table was given", config.name, name))
+            .id(name)
+            .unwrap_or_else(|| panic!("Map {} plays music track \"{}\", which is not in the music table", config.name, name)),
+        None => gba_map.music,
+    };
+
+    let settings = &config.settings;
+
+    // going through the id makes other(3) the same as the named weather or map type with id 3
+    let header = MapHeader {
+        weather: Weather::from(settings.weather.map(|weather| weather.id()).unwrap_or(gba_map.weather)),
+        map_type: MapType::from(settings.map_type.map(|map_type| map_type.id()).unwrap_or(gba_map.map_type)),
+        show_name: settings.show_name.unwrap_or(gba_map.show_name),
+        battle_scene: settings.battle_scene.unwrap_or(gba_map.battle_scene),
+    };
+
+    let extensions = MapExtensions {
+        encounters: super::wild::load_encounter_tables(config.wild.as_ref(), &sources.wild_dir),
+        variants: super::wild::load_encounter_variants(&sources.wild_dir),
+        header,
+    };
 
     (
         config.identifier,
         WorldMap {
             name: config.name,
-            music: gba_map.music,
+            music,
             width: gba_map.width,
             height: gba_map.height,
             tiles: gba_map.tiles,
             movements: gba_map.movements,
             border: Border {
-                tiles: gba_map.borders.into(),
-                size: (gba_map.borders.len() as f32).sqrt() as u8,
+                tiles: gba_map.borders,
+                // the border's height is its number of tiles divided by its width
+                size: gba_map.border_width,
             },
-            warps: super::warp::load_warp_entries(root_path.join("warps")),
-            wild: super::wild::load_wild_entry(config.wild, root_path.join("wild")),
-            npc_manager: super::npc::load_npc_entries(root_path.join("npcs")),
-            scripts: super::script::load_script_entries(root_path.join("scripts")),
-        }
+            warps: super::warp::load_warp_entries(root_path.join("warps"), template.map(|template| template.join("warps"))),
+            wild: super::wild::load_wild_entry(config.wild, sources.wild_dir.clone()),
+            npc_manager: super::npc::load_npc_entries(root_path.join("npcs"), template.map(|template| template.join("npcs"))),
+            scripts: super::script::load_script_entries(root_path.join("scripts"), template.map(|template| template.join("scripts"))),
+        },
+        extensions,
     )
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn discover_skips_map_parts() {
+        let maps = std::env::temp_dir().join(format!("world-builder-discover-{}", std::process::id()));
+        let town = maps.join("kanto").join("town");
+        // a template without a template.ron, only found through the maps that extend it
+        let template = maps.join("kanto").join("house_template");
+        for dir in [town.join("warps"), template.join("warps"), template.join("npcs")].iter() {
+            std::fs::create_dir_all(dir).unwrap();
+        }
+        for file in [town.join("town.ron"), town.join("warps").join("exit.ron"), template.join("warps").join("door.ron"), template.join("npcs").join("mom.ron")].iter() {
+            std::fs::write(file, "").unwrap();
+        }
+
+        let mut found = Vec::new();
+        for dir in list_dirs(&maps) {
+            discover(&maps, &dir, &MapFilter::new(&[], &[]), &mut found);
+        }
+        std::fs::remove_dir_all(&maps).unwrap();
+
+        assert_eq!(found, vec![(town.clone(), vec![town.join("town.ron")])]);
+    }
+
+}
@@ -1,42 +1,95 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use firecore_world_lib::map::MapIdentifier;
+use firecore_world_lib::map::{MapIdentifier, WorldMap};
 use firecore_world_lib::map::set::WorldMapSet;
 use ahash::AHashMap as HashMap;
 use crate::world::{SerializedMapSet, MapConfig};
+use crate::extension::MapExtensions;
+use crate::gba_map::PaletteLayout;
+use crate::music::MusicTable;
 
-pub fn load_map_set(root_path: &PathBuf, palette_sizes: &HashMap<u8, u16>, serialized_map_set: SerializedMapSet) -> (MapIdentifier, WorldMapSet) {
+pub fn load_map_set(root_path: &PathBuf, palettes: &PaletteLayout, music: Option<&MusicTable>, serialized_map_set: SerializedMapSet, errors: &mut u32) -> (MapIdentifier, WorldMapSet, HashMap<MapIdentifier, MapExtensions>) {
 
     println!("    Loading map set \"{}\"", serialized_map_set.identifier);
 
     let mut maps = HashMap::new();
+    let mut extensions = HashMap::new();
 
-    for dir_string in serialized_map_set.dirs {
-        let map_path = root_path.join(dir_string);
-        for dir_entry in std::fs::read_dir(&map_path).unwrap_or_else(|err| panic!("Could not read map set directory at {:?} with error {}", map_path, err)) {
-            let file = dir_entry.unwrap_or_else(|err| panic!("Could not read map set directory entry at {:?} with error {}", map_path, err)).path();
-            if let Some(ext) = file.extension() {
-                if ext == std::ffi::OsString::from("ron") {
-                    let config: MapConfig = ron::from_str(
-                        &std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not read map set configuration at {:?} to string with error {}", file, err))
-                    ).unwrap_or_else(|err| panic!("Could not deserialize map set configuration at {:?} with error {}", file, err));
+    *errors += load_map_set_maps(root_path, palettes, music, &serialized_map_set, "", &mut maps, &mut extensions);
+
+    (
+        serialized_map_set.identifier,
+        WorldMapSet::new(maps),
+        extensions,
+    )
+
+}
+
+/// Loads the maps in the directories of a map set, adding the maps of nested map sets to it as well.
+/// Maps of a nested map set have their identifier prefixed with the nested set's, such as `building_1/floor_1/room_1`.
+/// Returns the number of maps left out because their prefixed identifier is too long.
+fn load_map_set_maps(
+    root_path: &Path,
+    palettes: &PaletteLayout,
+    music: Option<&MusicTable>,
+    serialized_map_set: &SerializedMapSet,
+    prefix: &str,
+    maps: &mut HashMap<MapIdentifier, WorldMap>,
+    extensions: &mut HashMap<MapIdentifier, MapExtensions>,
+) -> u32 {
+
+    let mut errors = 0;
+
+    let dirs = match serialized_map_set.dirs.is_empty() {
+        true => super::list_dirs(root_path).into_iter().filter(|dir| !crate::world::template::is_template(dir) && !super::is_map_part(dir)).collect(),
+        false => serialized_map_set.dirs.iter().map(|dir| root_path.join(dir)).collect(),
+    };
+
+    for map_path in dirs {
+        if !map_path.is_dir() {
+            panic!("Map set {} lists directory {:?}, which does not exist", serialized_map_set.identifier, map_path);
+        }
+        for file in super::ron_files(&map_path) {
+            let data = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not read map set configuration at {:?} to string with error {}", file, err));
+            match ron::from_str::<MapConfig>(&data) {
+                Ok(config) => {
                     println!("        Loaded map set map \"{}\"", config.name);
-                    let (identifier, map) = super::load_map_from_config(&map_path, palette_sizes, config);
+                    let (identifier, map, map_extensions) = super::load_map_from_config(&map_path, palettes, music, config);
+                    let identifier = match prefix.is_empty() {
+                        true => identifier,
+                        false => match format!("{}{}", prefix, identifier).parse() {
+                            Ok(identifier) => identifier,
+                            Err(_) => {
+                                eprintln!("Map at {:?} in nested map set {} has identifier \"{}{}\", which is too long for a map identifier", file, serialized_map_set.identifier, prefix, identifier);
+                                errors += 1;
+                                continue;
+                            }
+                        },
+                    };
+                    if maps.contains_key(&identifier) {
+                        panic!("Map set {} has more than one map with identifier {}, the second at {:?}", serialized_map_set.identifier, identifier, file);
+                    }
+                    extensions.insert(identifier.clone(), map_extensions);
                     maps.insert(
                         identifier,
                         map,
                     );
                 }
+                Err(map_err) => match ron::from_str::<SerializedMapSet>(&data) {
+                    Ok(nested) => {
+                        println!("        Loading nested map set \"{}\"", nested.identifier);
+                        let prefix = format!("{}{}/", prefix, nested.identifier);
+                        errors += load_map_set_maps(&map_path, palettes, music, &nested, &prefix, maps, extensions);
+                    }
+                    Err(set_err) => panic!(
+                        "Could not deserialize map set configuration at {:?} as a map with error {} or as a nested map set with error {}",
+                        file, map_err, set_err
+                    ),
+                },
             }
         }
-
-        
-        
     }
 
-    (
-        serialized_map_set.identifier,
-        WorldMapSet::new(maps)
-    )
+    errors
 
-}
\ No newline at end of file
+}
@@ -1,6 +1,13 @@
+use std::convert::TryFrom;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
 use firecore_world_lib::map::MapIdentifier;
 use firecore_world_lib::map::chunk::Connections;
-use serde::Deserialize;
+use serde::{Serialize, Deserialize};
+
+use crate::extension::{EncounterKind, Weather, MapType};
 
 use firecore_util::Coordinate;
 
@@ -10,22 +17,57 @@ pub mod wild;
 pub mod warp;
 pub mod npc;
 pub mod script;
+pub mod template;
 
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct MapConfig {
 
     pub identifier: MapIdentifier,
     pub name: String,
+    /// Map file in the map's directory. Can be left out if the template has one.
+    #[serde(default)]
     pub file: String,
+    /// Template directory, relative to the map's directory, to take settings, warps, NPCs and scripts from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
 
     #[serde(default)]
     pub settings: SerializedMapSettings,
     pub wild: Option<SerializedWildEntry>,
+    /// Palettes the tile ids in the map file refer to. Defaults to the primary and secondary palettes in the map file.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tilesets: Option<Vec<SerializedTileset>>,
+    /// Size of the border in the map file, in case its header does not store one. Defaults to 2x2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<SerializedBorderSize>,
+    /// Name of the track in the music table to play instead of the one in the map file
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub music: Option<String>,
+
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SerializedBorderSize {
+
+    pub width: u8,
+    pub height: u8,
+
+}
+
+/// A palette whose tiles a map file refers to by the ids `start..start + length`.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct SerializedTileset {
+
+    pub palette: u8,
+    pub start: u16,
+    /// Defaults to the number of tiles in the palette
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub length: Option<u16>,
 
 }
 
 #[serde(deny_unknown_fields)]
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct SerializedChunkMap {
 
     pub config: MapConfig,
@@ -36,27 +78,201 @@ pub struct SerializedChunkMap {
 }
 
 #[serde(deny_unknown_fields)]
-#[derive(Deserialize)]
+#[derive(Serialize, Deserialize)]
 pub struct SerializedMapSet {
 
     pub identifier: MapIdentifier,
+    /// Directories of the maps in the set. Each holds either a map or a nested map set, whose maps are added to this set
+    /// under `<nested set identifier>/<map identifier>`. Every subdirectory except map templates is used if left out.
+    #[serde(default)]
     pub dirs: Vec<String>,
 
 }
 
-#[derive(Default, Deserialize)]
+/// Settings of a map. The header fields override the ones in the map file.
+#[derive(Default, Serialize, Deserialize)]
 pub struct SerializedMapSettings {
 
     pub fly_position: Option<Coordinate>,
 
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weather: Option<Weather>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map_type: Option<MapType>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub show_name: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub battle_scene: Option<u8>,
+
+}
+
+impl SerializedMapSettings {
+
+    /// Fills in the settings that are not set with the ones from `other`.
+    pub fn or(self, other: Self) -> Self {
+        Self {
+            fly_position: self.fly_position.or(other.fly_position),
+            weather: self.weather.or(other.weather),
+            map_type: self.map_type.or(other.map_type),
+            show_name: self.show_name.or(other.show_name),
+            battle_scene: self.battle_scene.or(other.battle_scene),
+        }
+    }
+
+}
+
+/// Shared part of a map, loaded from `template.ron` in the directory of a map template.
+/// Fields set in the map's own configuration win. Map discovery skips a template's warps, NPCs and scripts,
+/// but a template kept among the map directories with other `.ron` files needs a `template.ron` (which may be just `()`).
+#[serde(deny_unknown_fields)]
+#[derive(Default, Deserialize)]
+pub struct MapTemplate {
+
+    /// Map file in the template's directory
+    #[serde(default)]
+    pub file: Option<String>,
+    #[serde(default)]
+    pub settings: SerializedMapSettings,
+    #[serde(default)]
+    pub wild: Option<SerializedWildEntry>,
+    #[serde(default)]
+    pub tilesets: Option<Vec<SerializedTileset>>,
+    #[serde(default)]
+    pub border: Option<SerializedBorderSize>,
+    #[serde(default)]
+    pub music: Option<String>,
+
 }
 
-#[derive(Deserialize, Clone)]
+/// Lists the files in a directory along with the files of the same directory in a template,
+/// leaving out template files that have the same name as one in the directory. Sorted by file name.
+pub(crate) fn entry_files(dir: &Path, template: Option<&Path>) -> Vec<PathBuf> {
+    let mut files = list_files(dir, &[RON_EXTENSION]);
+    if let Some(template) = template {
+        let local: HashSet<OsString> = files.iter().filter_map(|file| file.file_name()).map(OsStr::to_os_string).collect();
+        files.extend(list_files(template, &[RON_EXTENSION]).into_iter().filter(|file| file.file_name().map(|name| !local.contains(name)).unwrap_or_default()));
+    }
+    // sorted so entries keep the same order between builds
+    files.sort_by(|a, b| a.file_name().cmp(&b.file_name()));
+    files
+}
+
+/// Extension of map configurations and of warp, NPC and script files
+pub(crate) const RON_EXTENSION: &str = "ron";
+/// Extension of the map files next to map configurations
+pub(crate) const MAP_EXTENSION: &str = "map";
+
+/// Files that file managers and version control leave in directories, which are skipped without a warning.
+const IGNORED_FILES: &[&str] = &[".DS_Store", ".gitkeep", ".gitignore", "Thumbs.db", "desktop.ini"];
+
+/// Files in a directory with one of the expected extensions, sorted. Other files are skipped with a warning,
+/// except the ones in `IGNORED_FILES`. Subdirectories are left out, and a missing directory has no files.
+pub(crate) fn list_files(dir: &Path, expected: &[&str]) -> Vec<PathBuf> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return Vec::new(),
+    };
+    let mut files = Vec::new();
+    for path in entries.filter_map(|entry| entry.ok()).map(|entry| entry.path()) {
+        if !path.is_file() || is_ignored(&path) {
+            continue;
+        }
+        match unexpected_file(&path, expected) {
+            false => files.push(path),
+            true => eprintln!("Warning: ignoring {:?}, only .{} files are read from {:?}", path, expected.join(" and ."), dir),
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Whether a file is one of `IGNORED_FILES`.
+fn is_ignored(path: &Path) -> bool {
+    path.file_name().and_then(OsStr::to_str).map(|name| IGNORED_FILES.contains(&name)).unwrap_or_default()
+}
+
+/// Whether `list_files` warns about a file, which is when it is neither ignored nor has an expected extension.
+fn unexpected_file(path: &Path, expected: &[&str]) -> bool {
+    !is_ignored(path) && !path.extension().and_then(OsStr::to_str).map(|extension| expected.contains(&extension)).unwrap_or_default()
+}
+
+#[derive(Serialize, Deserialize, Clone)]
 pub struct SerializedWildEntry {
 
     #[serde(rename = "type")]
-    pub encounter_type: String,
+    pub encounter_type: WildEncounterType,
     #[serde(default)]
     pub tiles: Option<Vec<u16>>,
+    /// Tiles that trigger the encounter tables other than grass
+    #[serde(default)]
+    pub encounter_tiles: HashMap<EncounterKind, Vec<u16>>,
     
-}
\ No newline at end of file
+}
+
+/// Where the grass encounter table of a map comes from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum WildEncounterType {
+
+    /// The table in the map's `wild/grass.toml`, which must exist
+    Original,
+    /// No grass encounters, for maps that only have surfing, fishing or other tables
+    None,
+
+}
+
+impl WildEncounterType {
+
+    pub const ALL: [WildEncounterType; 2] = [WildEncounterType::Original, WildEncounterType::None];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            WildEncounterType::Original => "original",
+            WildEncounterType::None => "none",
+        }
+    }
+
+}
+
+impl TryFrom<String> for WildEncounterType {
+    type Error = String;
+
+    fn try_from(name: String) -> Result<Self, Self::Error> {
+        Self::ALL.iter().find(|encounter_type| encounter_type.name() == name).copied().ok_or_else(|| format!(
+            "Unknown wild encounter type \"{}\", expected one of {:?}",
+            name,
+            Self::ALL.iter().map(WildEncounterType::name).collect::<Vec<_>>(),
+        ))
+    }
+}
+
+impl From<WildEncounterType> for String {
+    fn from(encounter_type: WildEncounterType) -> Self {
+        encounter_type.name().to_owned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn list_files_warns_about_unexpected_files() {
+        let expected = [RON_EXTENSION, MAP_EXTENSION];
+        assert!(!unexpected_file(Path::new("maps/town/town.ron"), &expected));
+        assert!(!unexpected_file(Path::new("maps/town/.DS_Store"), &expected));
+        assert!(unexpected_file(Path::new("maps/town/.town.ron.swp"), &expected));
+        assert!(unexpected_file(Path::new("maps/town/notes.txt"), &expected));
+
+        let dir = std::env::temp_dir().join(format!("world-builder-list-files-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        for name in ["town.ron", "town.map", ".DS_Store", ".town.ron.swp"].iter() {
+            std::fs::write(dir.join(name), "").unwrap();
+        }
+        let files = list_files(&dir, &expected);
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(files, vec![dir.join("town.map"), dir.join("town.ron")]);
+    }
+
+}
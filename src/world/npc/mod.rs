@@ -6,24 +6,29 @@ use firecore_world_lib::map::npc::NPCManager;
 
 pub mod npc_type;
 
-pub fn load_npc_entries(npc_path: PathBuf) -> NPCManager {
+/// Loads the NPCs of a map on top of the NPCs of its template, so the map's NPCs replace template NPCs with the same index.
+pub fn load_npc_entries(npc_path: PathBuf, template_path: Option<PathBuf>) -> NPCManager {
     let mut npcs = HashMap::new();
-    if let Ok(dir) = std::fs::read_dir(npc_path) {
-        for entry in dir {
-            if let Ok(entry) = entry {
-                let file = entry.path();
-                let data = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not get NPC file at {:?} with error {}", file, err));
-                let npc_result: Result<SerializedNPC, ron::Error> = ron::from_str(&data);
-                match npc_result {
-                    Ok(npc) => {
-                        npcs.insert(npc.index, npc.npc);
-                    },
-                    Err(err) => {
-                        panic!("Could not parse NPC at {:?} with error {} at position {}", file, err, err.position);
-                    },
-                }
-            }
-        }
-    } 
+    let template = template_path.map(load_npc_dir).unwrap_or_default();
+    for npc in template.into_iter().chain(load_npc_dir(npc_path)) {
+        npcs.insert(npc.index, npc.npc);
+    }
     NPCManager::new(npcs)
-}
\ No newline at end of file
+}
+
+fn load_npc_dir(npc_path: PathBuf) -> Vec<SerializedNPC> {
+    let mut npcs = Vec::new();
+    for file in super::list_files(&npc_path, &[super::RON_EXTENSION]) {
+        let data = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not get NPC file at {:?} with error {}", file, err));
+        let npc_result: Result<SerializedNPC, ron::Error> = ron::from_str(&data);
+        match npc_result {
+            Ok(npc) => {
+                npcs.push(npc);
+            },
+            Err(err) => {
+                panic!("Could not parse NPC at {:?} with error {} at position {}", file, err, err.position);
+            },
+        }
+    }
+    npcs
+}
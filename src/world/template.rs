@@ -0,0 +1,78 @@
+use std::path::{Path, PathBuf};
+
+use super::{MapConfig, MapTemplate};
+
+/// File that marks a directory as a map template.
+pub const TEMPLATE_FILE: &str = "template.ron";
+
+/// Whether a directory is a map template rather than a map, so map discovery leaves it alone.
+pub fn is_template(dir: &Path) -> bool {
+    dir.join(TEMPLATE_FILE).is_file()
+}
+
+/// Where the parts of a map are read from once its template is applied.
+pub struct MapSources {
+
+    /// Directory of the map file
+    pub file_dir: PathBuf,
+    /// Directory of the wild encounter tables
+    pub wild_dir: PathBuf,
+    /// Template directory, whose warps, NPCs and scripts are merged with the map's own
+    pub template: Option<PathBuf>,
+
+}
+
+/// Fills in the parts of a map configuration that its template has and it does not.
+pub fn apply_template(root_path: &Path, config: &mut MapConfig) -> MapSources {
+
+    let template_dir = match config.extends.as_ref() {
+        Some(extends) => root_path.join(extends),
+        None if config.file.is_empty() => panic!("Map {} has no map file", config.name),
+        None => return MapSources {
+            file_dir: root_path.to_path_buf(),
+            wild_dir: root_path.join("wild"),
+            template: None,
+        },
+    };
+
+    if !template_dir.is_dir() {
+        panic!("Map {} extends template {:?}, which is not a directory", config.name, template_dir);
+    }
+
+    let template_file = template_dir.join(TEMPLATE_FILE);
+    let template: MapTemplate = match template_file.is_file() {
+        true => ron::from_str(
+            &std::fs::read_to_string(&template_file).unwrap_or_else(|err| panic!("Could not read map template at {:?} with error {}", template_file, err))
+        ).unwrap_or_else(|err| panic!("Could not parse map template at {:?} with error {}", template_file, err)),
+        false => MapTemplate::default(),
+    };
+
+    let file_dir = match config.file.is_empty() {
+        true => {
+            config.file = template.file.unwrap_or_else(|| panic!("Map {} has no map file and neither does its template at {:?}", config.name, template_dir));
+            template_dir.clone()
+        }
+        false => root_path.to_path_buf(),
+    };
+
+    // the map's own wild encounters replace the template's as a whole
+    let wild_dir = match config.wild.is_some() || root_path.join("wild").is_dir() {
+        true => root_path.join("wild"),
+        false => {
+            config.wild = template.wild;
+            template_dir.join("wild")
+        }
+    };
+
+    config.settings = std::mem::take(&mut config.settings).or(template.settings);
+    config.tilesets = config.tilesets.take().or(template.tilesets);
+    config.border = config.border.or(template.border);
+    config.music = config.music.take().or(template.music);
+
+    MapSources {
+        file_dir,
+        wild_dir,
+        template: Some(template_dir),
+    }
+
+}
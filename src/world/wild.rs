@@ -1,16 +1,29 @@
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use firecore_world_lib::map::wild::{WildEntry, table::WildPokemonTable};
+use ahash::{AHashMap as HashMap, AHashSet as HashSet};
+use firecore_world_lib::TileId;
+use firecore_world_lib::map::wild::{WildEntry, WildPokemon, table::WildPokemonTable};
 
-use crate::world::SerializedWildEntry;
+use crate::extension::{EncounterKind, EncounterVariant, TimePeriod, Season};
+use crate::world::{SerializedWildEntry, WildEncounterType};
 
-pub fn load_wild_entry(wild: Option<SerializedWildEntry>, wild_path: PathBuf) -> Option<WildEntry> {
-    wild.map(|serialized_wild_entry| {
+/// Extension of wild pokemon tables
+const TOML_EXTENSION: &str = "toml";
+
+/// Chance (out of 100) of each encounter slot being picked, in slot order.
+pub const ENCOUNTER_CHANCES: [u8; 12] = [20, 20, 10, 10, 10, 10, 5, 5, 4, 4, 1, 1];
+
+pub const MAX_LEVEL: u8 = 100;
 
-        let file = wild_path.join("grass.toml");
+/// Highest encounter ratio, at which every step triggers an encounter.
+pub const MAX_ENCOUNTER_RATIO: u8 = 100;
 
-        let table = match serialized_wild_entry.encounter_type.as_str() {
-            "original" => {
+pub fn load_wild_entry(wild: Option<SerializedWildEntry>, wild_path: PathBuf) -> Option<WildEntry> {
+    wild.and_then(|serialized_wild_entry| {
+
+        let table = match serialized_wild_entry.encounter_type {
+            WildEncounterType::Original => {
+                let file = table_path(&wild_path, EncounterKind::Grass);
                 match std::fs::read_to_string(&file) {
                     Ok(content) => {
                         match toml::from_str(&content) {
@@ -21,20 +34,218 @@ pub fn load_wild_entry(wild: Option<SerializedWildEntry>, wild_path: PathBuf) ->
                         }
                     }
                     Err(err) => {
-                        eprintln!("Could not find wild toml file at {:?} with error {}", file, err);
-                        WildPokemonTable::default()
+                        panic!("Could not read wild pokemon table at {:?} required by the \"original\" encounter type with error {}", file, err);
                     }
                 }
             }
-            _ => {
-                WildPokemonTable::default()
+            WildEncounterType::None => return None,
+        };
+
+        Some(
+            WildEntry {
+                tiles: serialized_wild_entry.tiles,
+                table: table,
             }
+        )
+
+    })
+}
+
+/// Loads the surfing, fishing, rock smash and cave tables that exist under the wild directory.
+pub fn load_encounter_tables(wild: Option<&SerializedWildEntry>, wild_path: &Path) -> HashMap<EncounterKind, WildEntry> {
+    let mut tables = HashMap::new();
+    for kind in EncounterKind::ALL.iter().filter(|kind| **kind != EncounterKind::Grass) {
+        let file = table_path(wild_path, *kind);
+        if file.is_file() {
+            let content = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not read wild pokemon table at {:?} with error {}", file, err));
+            let table: WildPokemonTable = toml::from_str(&content).unwrap_or_else(|err| panic!("Could not parse wild pokemon table at {:?} with error {}", file, err));
+            tables.insert(
+                *kind,
+                WildEntry {
+                    tiles: wild.and_then(|wild| wild.encounter_tiles.get(kind).cloned()),
+                    table,
+                }
+            );
+        }
+    }
+    tables
+}
+
+/// Loads tables named like `grass.night.toml`, `surf.winter.toml` or `grass.night.winter.toml`
+/// that replace the base table of their kind during a time period and/or season.
+/// Panics on tables that are not named after an encounter type, such as `surfing.toml`.
+pub fn load_encounter_variants(wild_path: &Path) -> Vec<EncounterVariant> {
+    let mut variants: Vec<EncounterVariant> = Vec::new();
+    for file in super::list_files(wild_path, &[TOML_EXTENSION]) {
+        let filename = match file.file_name() {
+            Some(filename) => filename.to_string_lossy().into_owned(),
+            None => continue,
+        };
+        let mut parts: Vec<&str> = match filename.strip_suffix(".toml") {
+            Some(stem) => stem.split('.').collect(),
+            None => continue,
         };
+        let kind = parts.remove(0);
+        let kind = *EncounterKind::ALL.iter().find(|other| other.name() == kind).unwrap_or_else(|| panic!(
+            "Wild pokemon table at {:?} is not named after an encounter type ({:?})",
+            file,
+            EncounterKind::ALL.iter().map(EncounterKind::name).collect::<Vec<_>>(),
+        ));
+        // base tables are loaded by load_wild_entry and load_encounter_tables
+        if parts.is_empty() {
+            continue;
+        }
+        let mut time = None;
+        let mut season = None;
+        for part in parts {
+            if let Some(period) = TimePeriod::ALL.iter().find(|period| period.name() == part) {
+                if time.replace(*period).is_some() {
+                    panic!("Wild pokemon table at {:?} names more than one time period", file);
+                }
+            } else if let Some(other) = Season::ALL.iter().find(|other| other.name() == part) {
+                if season.replace(*other).is_some() {
+                    panic!("Wild pokemon table at {:?} names more than one season", file);
+                }
+            } else {
+                panic!("Wild pokemon table at {:?} has unknown variant \"{}\", expected a time period ({:?}) or season ({:?})",
+                    file,
+                    part,
+                    TimePeriod::ALL.iter().map(TimePeriod::name).collect::<Vec<_>>(),
+                    Season::ALL.iter().map(Season::name).collect::<Vec<_>>(),
+                );
+            }
+        }
+        if variants.iter().any(|variant| variant.kind == kind && variant.time == time && variant.season == season) {
+            panic!("Wild pokemon table at {:?} is a duplicate of another table with the same time period and season", file);
+        }
+        let content = std::fs::read_to_string(&file).unwrap_or_else(|err| panic!("Could not read wild pokemon table at {:?} with error {}", file, err));
+        let table = toml::from_str(&content).unwrap_or_else(|err| panic!("Could not parse wild pokemon table at {:?} with error {}", file, err));
+        variants.push(EncounterVariant {
+            kind,
+            time,
+            season,
+            table,
+        });
+    }
+    // sorted so variants keep the same order between builds
+    variants.sort_by_key(|variant| (variant.kind, variant.time, variant.season));
+    variants
+}
+
+pub fn table_path(wild_path: &Path, kind: EncounterKind) -> PathBuf {
+    wild_path.join(kind.name().to_owned() + ".toml")
+}
+
+pub fn variant_path(wild_path: &Path, variant: &EncounterVariant) -> PathBuf {
+    let mut filename = variant.kind.name().to_owned();
+    if let Some(time) = variant.time {
+        filename = filename + "." + time.name();
+    }
+    if let Some(season) = variant.season {
+        filename = filename + "." + season.name();
+    }
+    wild_path.join(filename + ".toml")
+}
+
+pub fn encounter_slots(table: &WildPokemonTable) -> &[WildPokemon] {
+    table.encounter.as_ref().map(|encounter| &encounter[..]).unwrap_or(&[])
+}
 
-        WildEntry {
-            tiles: serialized_wild_entry.tiles,
-            table: table,
+/// Checks an encounter table and the tiles it triggers on, returning a description of each problem found.
+pub fn verify_wild_entry(entry: &WildEntry, map_tiles: &[TileId]) -> Vec<String> {
+    let mut errors = verify_table(&entry.table);
+
+    if let Some(tiles) = entry.tiles.as_ref() {
+        let map_tiles: HashSet<TileId> = map_tiles.iter().copied().collect();
+        for tile in tiles.iter() {
+            if !map_tiles.contains(tile) {
+                errors.push(format!("triggers on tile #{} which does not appear on the map", tile));
+            }
         }
+    }
 
-    })
-}
\ No newline at end of file
+    errors
+}
+
+pub fn verify_table(table: &WildPokemonTable) -> Vec<String> {
+    let mut errors = Vec::new();
+
+    let slots = encounter_slots(table);
+
+    if slots.is_empty() {
+        if table.encounter_ratio != 0 {
+            errors.push(format!("has an encounter ratio of {} but no encounter slots", table.encounter_ratio));
+        }
+    } else {
+        // slots are picked by their weight in ENCOUNTER_CHANCES, which only add up to 100 over every slot
+        let covered: u32 = ENCOUNTER_CHANCES.iter().take(slots.len()).map(|chance| *chance as u32).sum();
+        if slots.len() < ENCOUNTER_CHANCES.len() {
+            errors.push(format!("has {} encounter slots, whose weights only add up to {} out of 100, so missing slots #{}-{} can be picked", slots.len(), covered, slots.len(), ENCOUNTER_CHANCES.len() - 1));
+        } else if slots.len() > ENCOUNTER_CHANCES.len() {
+            errors.push(format!("has {} encounter slots, but only the first {} have a weight, so the others are never picked", slots.len(), ENCOUNTER_CHANCES.len()));
+        }
+        if table.encounter_ratio == 0 {
+            errors.push(String::from("has encounter slots but an encounter ratio of 0, so they never trigger"));
+        }
+    }
+
+    if table.encounter_ratio > MAX_ENCOUNTER_RATIO {
+        errors.push(format!("has an encounter ratio of {}, above the maximum of {}", table.encounter_ratio, MAX_ENCOUNTER_RATIO));
+    }
+
+    for (index, slot) in slots.iter().enumerate() {
+        if slot.min_level > slot.max_level {
+            errors.push(format!("slot #{} has a minimum level ({}) above its maximum level ({})", index, slot.min_level, slot.max_level));
+        }
+        if slot.min_level == 0 || slot.max_level > MAX_LEVEL {
+            errors.push(format!("slot #{} has levels {}-{} outside of 1-{}", index, slot.min_level, slot.max_level, MAX_LEVEL));
+        }
+    }
+
+    errors
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn table(encounter_ratio: u8, slots: usize) -> WildPokemonTable {
+        let slot = "[[encounter]]\npokemon_id = 16\nmin_level = 2\nmax_level = 5\n";
+        toml::from_str(&format!("encounter_ratio = {}\n{}", encounter_ratio, slot.repeat(slots))).unwrap()
+    }
+
+    #[test]
+    fn encounter_chances_add_up() {
+        assert_eq!(ENCOUNTER_CHANCES.iter().map(|chance| *chance as u32).sum::<u32>(), 100);
+    }
+
+    #[test]
+    fn verify_table_checks_slot_weights() {
+        assert!(verify_table(&table(21, ENCOUNTER_CHANCES.len())).is_empty());
+        assert_eq!(verify_table(&table(21, 10)).len(), 1);
+        assert_eq!(verify_table(&table(21, 13)).len(), 1);
+    }
+
+    #[test]
+    fn verify_table_checks_encounter_ratio() {
+        assert!(verify_table(&table(MAX_ENCOUNTER_RATIO, ENCOUNTER_CHANCES.len())).is_empty());
+        assert_eq!(verify_table(&table(MAX_ENCOUNTER_RATIO + 1, ENCOUNTER_CHANCES.len())).len(), 1);
+        assert_eq!(verify_table(&table(0, ENCOUNTER_CHANCES.len())).len(), 1);
+        assert!(verify_table(&table(0, 0)).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "is not named after an encounter type")]
+    fn variants_reject_unknown_tables() {
+        let wild = std::env::temp_dir().join(format!("world-builder-wild-{}", std::process::id()));
+        std::fs::create_dir_all(&wild).unwrap();
+        std::fs::write(wild.join("surfing.toml"), "").unwrap();
+        let result = std::panic::catch_unwind(|| load_encounter_variants(&wild));
+        std::fs::remove_dir_all(&wild).unwrap();
+        if let Err(panic) = result {
+            std::panic::resume_unwind(panic);
+        }
+    }
+
+}